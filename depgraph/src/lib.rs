@@ -1,6 +1,153 @@
+use std::slice;
+
+/// Identifies a node within a `DepGraph`.
+#[derive(Clone, Copy, Debug, Eq, Hash, Ord, PartialEq, PartialOrd)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(&self) -> usize { self.0 }
+}
+
+/// The two kinds of edges jam records between targets: `DEPENDS` and
+/// `INCLUDES`.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub enum Edge {
+    Depends,
+    Includes,
+}
+
+#[derive(Debug)]
+struct Node<T> {
+    data: T,
+    depends: Vec<NodeId>,
+    includes: Vec<NodeId>,
+}
+
+/// A dependency graph whose nodes carry a `T` payload.
+#[derive(Debug)]
+pub struct DepGraph<T> {
+    nodes: Vec<Node<T>>,
+}
+
+impl<T> DepGraph<T> {
+    pub fn new() -> DepGraph<T> {
+        DepGraph { nodes: Vec::new() }
+    }
+
+    pub fn add_node(&mut self, data: T) -> NodeId {
+        self.nodes.push(Node { data, depends: Vec::new(), includes: Vec::new() });
+        NodeId(self.nodes.len() - 1)
+    }
+
+    /// Adds an edge from `from` to `to`.  Duplicate edges are ignored.
+    pub fn add_edge(&mut self, from: NodeId, to: NodeId, kind: Edge) {
+        let node = &mut self.nodes[from.0];
+        let edges = match kind {
+            Edge::Depends => &mut node.depends,
+            Edge::Includes => &mut node.includes,
+        };
+        if !edges.contains(&to) {
+            edges.push(to);
+        }
+    }
+
+    pub fn edges(&self, id: NodeId, kind: Edge) -> &[NodeId] {
+        match kind {
+            Edge::Depends => &self.nodes[id.0].depends,
+            Edge::Includes => &self.nodes[id.0].includes,
+        }
+    }
+
+    /// Returns the nodes reachable from `roots` along edges of either
+    /// kind, in depth-first preorder.
+    pub fn reachable(&self, roots: &[NodeId]) -> Vec<NodeId> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut order = Vec::new();
+        let mut stack: Vec<NodeId> = roots.iter().rev().cloned().collect();
+        while let Some(id) = stack.pop() {
+            if seen[id.0] {
+                continue;
+            }
+            seen[id.0] = true;
+            order.push(id);
+            let node = &self.nodes[id.0];
+            stack.extend(node.includes.iter().rev());
+            stack.extend(node.depends.iter().rev());
+        }
+        order
+    }
+
+    pub fn node(&self, id: NodeId) -> &T { &self.nodes[id.0].data }
+
+    pub fn node_mut(&mut self, id: NodeId) -> &mut T { &mut self.nodes[id.0].data }
+
+    pub fn ids(&self) -> NodeIds { NodeIds(0..self.nodes.len()) }
+
+    pub fn iter(&self) -> Nodes<'_, T> { Nodes(self.nodes.iter()) }
+
+    pub fn len(&self) -> usize { self.nodes.len() }
+
+    pub fn is_empty(&self) -> bool { self.nodes.is_empty() }
+}
+
+impl<T> Default for DepGraph<T> {
+    fn default() -> DepGraph<T> { DepGraph::new() }
+}
+
+pub struct NodeIds(::std::ops::Range<usize>);
+
+impl Iterator for NodeIds {
+    type Item = NodeId;
+    fn next(&mut self) -> Option<NodeId> { self.0.next().map(NodeId) }
+}
+
+pub struct Nodes<'a, T: 'a>(slice::Iter<'a, Node<T>>);
+
+impl<'a, T> Iterator for Nodes<'a, T> {
+    type Item = &'a T;
+    fn next(&mut self) -> Option<&'a T> { self.0.next().map(|n| &n.data) }
+}
+
 #[cfg(test)]
 mod tests {
+    use super::*;
+
+    #[test]
+    fn adding_nodes_and_edges() {
+        let mut g = DepGraph::new();
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        g.add_edge(a, b, Edge::Depends);
+        g.add_edge(a, b, Edge::Depends);
+        g.add_edge(b, c, Edge::Includes);
+        assert_eq!(g.len(), 3);
+        assert_eq!(g.edges(a, Edge::Depends), &[b]);
+        assert!(g.edges(a, Edge::Includes).is_empty());
+        assert_eq!(g.edges(b, Edge::Includes), &[c]);
+        assert_eq!(*g.node(c), "c");
+    }
+
+    #[test]
+    fn reachable_nodes() {
+        let mut g = DepGraph::new();
+        let a = g.add_node("a");
+        let b = g.add_node("b");
+        let c = g.add_node("c");
+        let d = g.add_node("d");
+        g.add_edge(a, b, Edge::Depends);
+        g.add_edge(b, a, Edge::Depends);
+        g.add_edge(a, c, Edge::Includes);
+        assert_eq!(g.reachable(&[a]), vec![a, b, c]);
+        assert_eq!(g.reachable(&[c, d]), vec![c, d]);
+    }
+
     #[test]
-    fn it_works() {
+    fn iterating_nodes() {
+        let mut g = DepGraph::new();
+        g.add_node(1);
+        g.add_node(2);
+        assert_eq!(g.ids().map(|id| id.index()).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(g.iter().cloned().collect::<Vec<_>>(), vec![1, 2]);
     }
 }
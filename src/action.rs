@@ -1,3 +1,6 @@
+use list::List;
+
+#[derive(Clone, Debug, PartialEq)]
 pub enum Flags {
     Updated,
     Together,
@@ -8,4 +11,42 @@ pub enum Flags {
     Maxline(u32),
 }
 
-pub struct Action {}
\ No newline at end of file
+/// An `actions` definition: the named command text and its modifiers.
+#[derive(Debug)]
+pub struct Action {
+    pub name: String,
+    pub flags: Vec<Flags>,
+    pub bind_list: List,
+    pub command: String,
+}
+
+impl Action {
+    pub fn new<T: ToString>(name: T, flags: Vec<Flags>, command: T) -> Action {
+        Action {
+            name: name.to_string(),
+            flags,
+            bind_list: List::new(),
+            command: command.to_string(),
+        }
+    }
+
+    pub fn has(&self, flag: Flags) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    pub fn maxline(&self) -> Option<usize> {
+        self.flags.iter()
+            .filter_map(|f| match *f { Flags::Maxline(n) => Some(n as usize), _ => None })
+            .next()
+    }
+}
+
+/// A call of an action on a set of targets and sources, made when a rule
+/// with the same name as the action is invoked.  The same invocation is
+/// shared by every target it updates.
+#[derive(Debug)]
+pub struct Invocation {
+    pub action: ::std::rc::Rc<Action>,
+    pub targets: List,
+    pub sources: List,
+}
@@ -0,0 +1,247 @@
+use std::cell::Cell;
+use std::io::{self, BufRead, BufReader, Write};
+use std::net::{TcpStream, ToSocketAddrs};
+use std::time::Duration;
+use hash::Digest;
+use super::CacheStore;
+
+/// How long to wait for the server to accept a connection.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(5);
+/// How long to wait for the server to take or send more data.
+const IO_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A cache on an HTTP server speaking the simple protocol of bazel-remote
+/// and similar caches: entries are fetched with `GET` and stored with
+/// `PUT` at `url/ac/key`.  Only plain `http://` is supported.
+///
+/// Entries are jam's own packed files, not the `ActionResult` messages
+/// bazel-remote expects under `/ac/`, so bazel-remote must be started with
+/// `--disable_http_ac_validation`.
+///
+/// If the server cannot be reached, the cache is turned off for the rest
+/// of the run rather than making every target wait for it.
+#[derive(Debug)]
+pub struct HttpCache {
+    host: String,
+    port: u16,
+    path: String,
+    disabled: Cell<bool>,
+}
+
+impl HttpCache {
+    pub fn new(url: &str) -> Result<HttpCache, String> {
+        let rest = url.strip_prefix("http://").ok_or_else(|| format!("unsupported cache URL {}", url))?;
+        let (authority, path) = match rest.find('/') {
+            Some(i) => (&rest[..i], rest[i..].trim_end_matches('/')),
+            None => (rest, ""),
+        };
+        let (host, port) = match authority.rfind(':') {
+            Some(i) => (&authority[..i], authority[i + 1..].parse()
+                .map_err(|_| format!("bad port in cache URL {}", url))?),
+            None => (authority, 80),
+        };
+        if host.is_empty() {
+            return Err(format!("no host in cache URL {}", url));
+        }
+        Ok(HttpCache { host: host.to_string(), port, path: path.to_string(), disabled: Cell::new(false) })
+    }
+
+    /// Sends one request and returns the status code and body, or `None`
+    /// if the cache has been turned off.  Failing to talk to the server at
+    /// all turns it off.
+    fn request(&self, method: &str, key: &Digest, body: &[u8]) -> io::Result<Option<(u32, Vec<u8>)>> {
+        if self.disabled.get() {
+            return Ok(None);
+        }
+        self.exchange(method, key, body).map(Some).map_err(|e| {
+            self.disabled.set(true);
+            error(format!("{}:{}: {}; not using the cache for the rest of this run", self.host, self.port, e))
+        })
+    }
+
+    fn exchange(&self, method: &str, key: &Digest, body: &[u8]) -> io::Result<(u32, Vec<u8>)> {
+        let mut stream = self.connect()?;
+        stream.set_read_timeout(Some(IO_TIMEOUT))?;
+        stream.set_write_timeout(Some(IO_TIMEOUT))?;
+        write!(stream, "{} {}/ac/{} HTTP/1.1\r\nHost: {}:{}\r\nContent-Length: {}\r\n\
+                        Connection: close\r\n\r\n",
+               method, self.path, key, self.host, self.port, body.len())?;
+        stream.write_all(body)?;
+        stream.flush()?;
+        read_response(BufReader::new(stream))
+    }
+
+    /// Connects to the first of the server's addresses that answers.
+    fn connect(&self) -> io::Result<TcpStream> {
+        let mut last = error(format!("no addresses for {}", self.host));
+        for addr in (self.host.as_str(), self.port).to_socket_addrs()? {
+            match TcpStream::connect_timeout(&addr, CONNECT_TIMEOUT) {
+                Ok(stream) => return Ok(stream),
+                Err(e) => last = e,
+            }
+        }
+        Err(last)
+    }
+}
+
+impl CacheStore for HttpCache {
+    fn get(&self, key: &Digest) -> io::Result<Option<Vec<u8>>> {
+        match self.request("GET", key, &[])? {
+            Some((200, body)) => Ok(Some(body)),
+            Some((404, _)) | None => Ok(None),
+            Some((status, _)) => Err(error(format!("cache server answered GET with {}", status))),
+        }
+    }
+
+    fn put(&self, key: &Digest, data: &[u8]) -> io::Result<()> {
+        match self.request("PUT", key, data)? {
+            Some((200..=299, _)) | None => Ok(()),
+            Some((status, _)) => Err(error(format!("cache server answered PUT with {}", status))),
+        }
+    }
+}
+
+fn error(msg: String) -> io::Error {
+    io::Error::other(msg)
+}
+
+fn read_response<R: BufRead>(mut r: R) -> io::Result<(u32, Vec<u8>)> {
+    let mut line = String::new();
+    r.read_line(&mut line)?;
+    let status = line.split_whitespace().nth(1).and_then(|s| s.parse().ok())
+        .ok_or_else(|| error(format!("bad HTTP status line {:?}", line.trim_end())))?;
+
+    let mut length = None;
+    let mut chunked = false;
+    loop {
+        line.clear();
+        if r.read_line(&mut line)? == 0 || line.trim_end().is_empty() {
+            break;
+        }
+        let (name, value) = match line.find(':') {
+            Some(i) => (line[..i].trim().to_ascii_lowercase(), line[i + 1..].trim().to_string()),
+            None => continue,
+        };
+        match name.as_str() {
+            "content-length" => length = value.parse::<usize>().ok(),
+            "transfer-encoding" => chunked = value.eq_ignore_ascii_case("chunked"),
+            _ => {}
+        }
+    }
+
+    let mut body = Vec::new();
+    if chunked {
+        loop {
+            line.clear();
+            r.read_line(&mut line)?;
+            let size = usize::from_str_radix(line.trim().split(';').next().unwrap_or(""), 16)
+                .map_err(|_| error("bad HTTP chunk".to_string()))?;
+            if size == 0 {
+                break;
+            }
+            let start = body.len();
+            body.resize(start + size, 0);
+            r.read_exact(&mut body[start..])?;
+            r.read_line(&mut line)?;
+        }
+    } else if let Some(length) = length {
+        body.resize(length, 0);
+        r.read_exact(&mut body)?;
+    } else {
+        r.read_to_end(&mut body)?;
+    }
+    Ok((status, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use hash::Hasher;
+    use std::collections::HashMap;
+    use std::io::Read;
+    use std::net::TcpListener;
+    use std::thread;
+
+    /// Serves `requests` requests like a bazel-remote cache would, keeping
+    /// entries in memory, and returns the URL to reach it on.
+    fn mock_server(requests: usize) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let url = format!("http://{}/cache/", listener.local_addr().unwrap());
+        thread::spawn(move || {
+            let mut entries: HashMap<String, Vec<u8>> = HashMap::new();
+            for stream in listener.incoming().take(requests) {
+                let stream = stream.unwrap();
+                let mut r = BufReader::new(stream.try_clone().unwrap());
+                let mut line = String::new();
+                r.read_line(&mut line).unwrap();
+                let fields: Vec<String> = line.split_whitespace().map(|s| s.to_string()).collect();
+                let mut length = 0;
+                loop {
+                    let mut header = String::new();
+                    r.read_line(&mut header).unwrap();
+                    if header.trim().is_empty() {
+                        break;
+                    }
+                    if let Some(v) = header.strip_prefix("Content-Length: ") {
+                        length = v.trim().parse().unwrap();
+                    }
+                }
+                let mut body = vec![0; length];
+                r.read_exact(&mut body).unwrap();
+
+                let mut stream = stream;
+                match (fields[0].as_str(), entries.get(&fields[1])) {
+                    ("PUT", _) => {
+                        entries.insert(fields[1].clone(), body);
+                        write!(stream, "HTTP/1.1 200 OK\r\nContent-Length: 0\r\n\r\n").unwrap();
+                    }
+                    ("GET", Some(data)) => {
+                        write!(stream, "HTTP/1.1 200 OK\r\nTransfer-Encoding: chunked\r\n\r\n\
+                                        {:x}\r\n", data.len()).unwrap();
+                        stream.write_all(data).unwrap();
+                        write!(stream, "\r\n0\r\n\r\n").unwrap();
+                    }
+                    _ => write!(stream, "HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\n\r\nnot found").unwrap(),
+                }
+            }
+        });
+        url
+    }
+
+    #[test]
+    fn getting_and_putting() {
+        let cache = HttpCache::new(&mock_server(3)).unwrap();
+        assert_eq!(cache.path, "/cache");
+        let key = Hasher::new().finish();
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.put(&key, b"some\r\ndata").unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(b"some\r\ndata".to_vec()));
+    }
+
+    #[test]
+    fn unreachable_servers_turn_the_cache_off() {
+        let addr = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let cache = HttpCache::new(&format!("http://{}", addr)).unwrap();
+        let key = Hasher::new().finish();
+        assert!(cache.get(&key).is_err());
+        assert!(cache.disabled.get());
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.put(&key, b"data").unwrap();
+    }
+
+    #[test]
+    fn parsing_urls() {
+        let cache = HttpCache::new("http://cache.example.com").unwrap();
+        assert_eq!((cache.host.as_str(), cache.port, cache.path.as_str()), ("cache.example.com", 80, ""));
+        assert!(HttpCache::new("https://cache.example.com").is_err());
+        assert!(HttpCache::new("http://cache:port/").is_err());
+        assert!(HttpCache::new("http://:80/").is_err());
+    }
+
+    #[test]
+    fn server_errors() {
+        let response = "HTTP/1.1 500 Internal Server Error\r\nContent-Length: 4\r\n\r\noops";
+        assert_eq!(read_response(response.as_bytes()).unwrap(), (500, b"oops".to_vec()));
+        assert!(read_response("garbage\r\n".as_bytes()).is_err());
+    }
+}
@@ -0,0 +1,60 @@
+use std::fs;
+use std::io;
+use std::path::PathBuf;
+use hash::Digest;
+use super::CacheStore;
+
+/// A cache in a local directory, one file per entry, which any number of
+/// checkouts on the machine can share.
+#[derive(Debug)]
+pub struct LocalCache {
+    dir: PathBuf,
+}
+
+impl LocalCache {
+    pub fn new<P: Into<PathBuf>>(dir: P) -> LocalCache {
+        LocalCache { dir: dir.into() }
+    }
+
+    fn path(&self, key: &Digest) -> PathBuf {
+        let key = key.to_string();
+        self.dir.join(&key[..2]).join(&key)
+    }
+}
+
+impl CacheStore for LocalCache {
+    fn get(&self, key: &Digest) -> io::Result<Option<Vec<u8>>> {
+        match fs::read(self.path(key)) {
+            Ok(data) => Ok(Some(data)),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+
+    fn put(&self, key: &Digest, data: &[u8]) -> io::Result<()> {
+        let path = self.path(key);
+        fs::create_dir_all(path.parent().unwrap())?;
+        // Written aside and renamed, so other builds never see half an entry.
+        let tmp = path.with_extension(format!("tmp{}", ::std::process::id()));
+        fs::write(&tmp, data)?;
+        fs::rename(&tmp, &path)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+    use hash::Hasher;
+
+    #[test]
+    fn storing_entries() {
+        let dir = TempDir::new("local-cache-test");
+        let cache = LocalCache::new(dir.path());
+        let key = Hasher::new().finish();
+        assert_eq!(cache.get(&key).unwrap(), None);
+        cache.put(&key, b"data").unwrap();
+        cache.put(&key, b"newer").unwrap();
+        assert_eq!(cache.get(&key).unwrap(), Some(b"newer".to_vec()));
+    }
+}
@@ -0,0 +1,166 @@
+//! Caching of built targets, so that an action whose inputs and command
+//! have been seen before can be skipped and its outputs restored instead.
+//!
+//! Entries are keyed by a digest of everything that goes into an action
+//! and hold the files it wrote, packed into a single blob.  Where they are
+//! kept is up to the `CacheStore`.
+
+use std::fs::{self, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::PermissionsExt;
+use std::path::Path;
+use env::Environment;
+use graph::Targets;
+use hash::{Digest, Hasher};
+use jam_depgraph::NodeId;
+use list::List;
+use state;
+
+mod http;
+mod local;
+
+pub use self::http::HttpCache;
+pub use self::local::LocalCache;
+
+/// Somewhere cache entries can be kept.
+pub trait CacheStore {
+    /// Fetches the entry stored under `key`, if there is one.
+    fn get(&self, key: &Digest) -> io::Result<Option<Vec<u8>>>;
+
+    /// Stores `data` under `key`, replacing any entry already there.
+    fn put(&self, key: &Digest, data: &[u8]) -> io::Result<()>;
+}
+
+/// The files updating target `id` writes: the bound names of every
+/// target of its actions.
+pub fn outputs(targets: &Targets, id: NodeId) -> List {
+    let mut outputs = List::new();
+    for inv in &targets.get(id).actions {
+        for name in inv.targets.iter() {
+            let bound = targets.find(name).map_or(name.as_str(), |t| targets.get(t).bound_name());
+            if !outputs.iter().any(|o| o == bound) {
+                outputs.append(bound);
+            }
+        }
+    }
+    outputs
+}
+
+/// The cache key for target `id`: its inputs, its actions and where its
+/// outputs go.
+pub fn key(targets: &Targets, env: &Environment, id: NodeId) -> Digest {
+    let mut h = Hasher::new();
+    h.update_str(&state::inputs(targets, id).to_string());
+    h.update_str(&state::commands(targets, env, id).to_string());
+    for output in outputs(targets, id).iter() {
+        h.update_str(output);
+    }
+    h.finish()
+}
+
+/// Packs the files `paths` into one blob: for each a `length mode path`
+/// line, with the permission bits in octal, followed by its contents.
+pub fn pack(paths: &List) -> io::Result<Vec<u8>> {
+    let mut data = Vec::new();
+    for path in paths.iter() {
+        let mut file = File::open(path)?;
+        let mode = file.metadata()?.permissions().mode() & 0o7777;
+        let mut contents = Vec::new();
+        file.read_to_end(&mut contents)?;
+        writeln!(data, "{} {:o} {}", contents.len(), mode, path)?;
+        data.extend_from_slice(&contents);
+    }
+    Ok(data)
+}
+
+/// Writes back the files in a blob made by `pack`, which may only name
+/// `outputs`, those of the target it was looked up for.  Nothing is
+/// written unless the whole blob is sound.
+pub fn unpack(mut data: &[u8], outputs: &List) -> io::Result<()> {
+    let bad = |why: String| io::Error::new(io::ErrorKind::InvalidData, why);
+    let corrupt = || bad("corrupt cache entry".to_string());
+    let mut files = Vec::new();
+    while !data.is_empty() {
+        let eol = data.iter().position(|&b| b == b'\n').ok_or_else(corrupt)?;
+        let header = String::from_utf8_lossy(&data[..eol]).into_owned();
+        let mut fields = header.splitn(3, ' ');
+        let length: usize = fields.next().and_then(|l| l.parse().ok()).ok_or_else(corrupt)?;
+        let mode = fields.next().and_then(|m| u32::from_str_radix(m, 8).ok()).ok_or_else(corrupt)?;
+        let path = fields.next().ok_or_else(corrupt)?.to_string();
+        let contents = data.get(eol + 1..eol + 1 + length).ok_or_else(corrupt)?;
+        if !outputs.iter().any(|o| *o == path) {
+            return Err(bad(format!("cache entry writes {}, which is not an output", path)));
+        }
+        files.push((path, mode, contents));
+        data = &data[eol + 1 + length..];
+    }
+
+    for (path, mode, contents) in files {
+        if let Some(dir) = Path::new(&path).parent() {
+            if !dir.as_os_str().is_empty() {
+                fs::create_dir_all(dir)?;
+            }
+        }
+        // Written aside and renamed, so a failed restore never leaves half
+        // an output behind.
+        let tmp = format!("{}.tmp{}", path, ::std::process::id());
+        fs::write(&tmp, contents)?;
+        fs::set_permissions(&tmp, fs::Permissions::from_mode(mode))?;
+        fs::rename(&tmp, &path)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+
+    #[test]
+    fn packing_and_unpacking() {
+        let dir = TempDir::new("cache-pack-test");
+        let a = dir.name("a.o");
+        let b = dir.join("sub dir").join("b.o").to_string_lossy().into_owned();
+        fs::create_dir_all(dir.join("sub dir")).unwrap();
+        fs::write(&a, "a\n").unwrap();
+        fs::write(&b, "").unwrap();
+
+        let outputs = List::from(vec![a.as_str(), b.as_str()]);
+        let data = pack(&outputs).unwrap();
+        fs::remove_dir_all(dir.path()).unwrap();
+        unpack(&data, &outputs).unwrap();
+        assert_eq!(fs::read_to_string(&a).unwrap(), "a\n");
+        assert_eq!(fs::read_to_string(&b).unwrap(), "");
+        fs::remove_dir_all(dir.path()).unwrap();
+
+        assert!(unpack(b"10 644 short\nabc", &List::from(vec!["short"])).is_err());
+        assert!(unpack(b"1 short\na", &List::from(vec!["short"])).is_err());
+        assert!(pack(&List::from(vec![a.as_str()])).is_err());
+    }
+
+    #[test]
+    fn unpacking_restores_modes() {
+        let dir = TempDir::new("cache-mode-test");
+        let prog = dir.name("prog");
+        fs::write(&prog, "#!/bin/sh\n").unwrap();
+        fs::set_permissions(&prog, fs::Permissions::from_mode(0o755)).unwrap();
+
+        let outputs = List::from(vec![prog.as_str()]);
+        let data = pack(&outputs).unwrap();
+        fs::remove_file(&prog).unwrap();
+        unpack(&data, &outputs).unwrap();
+        assert_eq!(fs::metadata(&prog).unwrap().permissions().mode() & 0o7777, 0o755);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[test]
+    fn unpacking_only_writes_outputs() {
+        let dir = TempDir::new("cache-unpack-test");
+        let out = dir.name("out.o");
+        let evil = dir.name("evil");
+        let data = format!("1 644 {}\na1 644 {}\nb", out, evil);
+        let err = unpack(data.as_bytes(), &List::from(vec![out.as_str()])).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(!Path::new(&out).exists() && !Path::new(&evil).exists());
+    }
+}
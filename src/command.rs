@@ -0,0 +1,348 @@
+use std::collections::{HashMap, HashSet};
+use std::env;
+use std::fmt;
+use std::fs::OpenOptions;
+use std::io::{self, Write};
+use std::os::unix::fs::OpenOptionsExt;
+use std::path::PathBuf;
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use action::{Action, Flags, Invocation};
+use env::Environment;
+use expand;
+use graph::Targets;
+use jam_depgraph::NodeId;
+use list::List;
+use variable::Variable;
+
+/// The longest command jam will run when an action has no `maxline`.
+pub const MAXLINE: usize = 10240;
+
+/// A fully expanded action, ready to run.
+#[derive(Debug)]
+pub struct Command {
+    pub action: Rc<Action>,
+    /// The target being made, used in the `Rule target` header.
+    pub target: String,
+    pub targets: List,
+    pub sources: List,
+    pub text: String,
+    /// The response file the sources were moved to, if the command was
+    /// too long to name them.
+    pub response: Option<Response>,
+}
+
+/// A file holding the sources of a command, one per line, passed to it as
+/// `$(JAMRESPONSE)file` in place of `$(>)`.  It is written just before the
+/// command runs and removed after.
+#[derive(Debug, PartialEq)]
+pub struct Response {
+    pub path: PathBuf,
+    pub contents: String,
+}
+
+impl Command {
+    pub fn quiet(&self) -> bool { self.action.has(Flags::Quietly) }
+
+    pub fn ignore(&self) -> bool { self.action.has(Flags::Ignore) }
+
+    /// Writes the response file, if the command has one, readable by its
+    /// owner alone.  Its name can be guessed, so the file is only ever
+    /// created afresh: if something is there already, the response and the
+    /// command move to another name.
+    pub fn write_response(&mut self) -> io::Result<()> {
+        let response = match self.response {
+            Some(ref mut response) => response,
+            None => return Ok(()),
+        };
+        loop {
+            let file = OpenOptions::new().write(true).create_new(true).mode(0o600).open(&response.path);
+            match file {
+                Ok(mut file) => return file.write_all(response.contents.as_bytes()),
+                Err(ref e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    let path = response_path();
+                    self.text = self.text.replace(&*response.path.to_string_lossy(), &path.to_string_lossy());
+                    response.path = path;
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum CommandError {
+    TooLong { action: String, target: String, length: usize },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            CommandError::TooLong { ref action, ref target, length } =>
+                write!(f, "command line too long ({} bytes) for {} {}", length, action, target),
+        }
+    }
+}
+
+/// Builds the commands needed to update target `id`, honouring the
+/// `together`, `updated`, `existing` and `piecemeal` modifiers.  A command
+/// too long for `maxline` that cannot be split gets its sources from a
+/// response file instead, if `JAMRESPONSE` names the prefix the tool
+/// expects, usually `@`.  Each
+/// invocation is only built once even when it updates several targets;
+/// `done` records those already handled.
+pub fn build(targets: &Targets, env: &Environment, id: NodeId,
+             done: &mut HashSet<*const Invocation>) -> Result<Vec<Command>, CommandError> {
+    let target = targets.get(id);
+    let scope = target.scope(env);
+    let mut commands = Vec::new();
+
+    for (i, inv) in target.actions.iter().enumerate() {
+        if !done.insert(&**inv as *const Invocation) {
+            continue;
+        }
+        let action = &inv.action;
+        let mut sources = inv.sources.clone();
+        if action.has(Flags::Together) {
+            for other in &target.actions[i + 1..] {
+                if Rc::ptr_eq(&other.action, action) && other.targets == inv.targets
+                        && done.insert(&**other as *const Invocation) {
+                    for s in other.sources.iter() {
+                        if !sources.iter().any(|x| x == s) {
+                            sources.append(s);
+                        }
+                    }
+                }
+            }
+        }
+        let sources = filter_sources(targets, action, &sources);
+        if (action.has(Flags::Updated) || action.has(Flags::Existing)) && sources.is_empty() {
+            continue;
+        }
+
+        let maxline = action.maxline().unwrap_or(MAXLINE);
+        let response = scope.get("JAMRESPONSE").and_then(|v| v.value().get(0).map(|s| s.to_string()));
+        let mut start = 0;
+        loop {
+            let mut chunk = sources.length() - start;
+            let mut cmd = command(targets, &scope, inv, &sources.sublist(start, chunk), None);
+            while cmd.text.len() > maxline && action.has(Flags::Piecemeal) && chunk > 1 {
+                chunk = (chunk * 9 / 10).max(1).min(chunk - 1);
+                cmd = command(targets, &scope, inv, &sources.sublist(start, chunk), None);
+            }
+            if cmd.text.len() > maxline && !action.has(Flags::Piecemeal) && response.is_some() {
+                cmd = command(targets, &scope, inv, &sources, response.as_deref());
+            }
+            if cmd.text.len() > maxline {
+                return Err(CommandError::TooLong {
+                    action: action.name.clone(),
+                    target: cmd.target.clone(),
+                    length: cmd.text.len(),
+                });
+            }
+            commands.push(cmd);
+            start += chunk;
+            if start >= sources.length() {
+                break;
+            }
+        }
+    }
+    Ok(commands)
+}
+
+/// The text of every action on target `id` expanded with all of its
+/// sources, whatever `updated`, `existing` or `piecemeal` would pick on a
+/// given run, so that it changes only when the Jamfile or its variables do.
+pub fn signature(targets: &Targets, env: &Environment, id: NodeId) -> Vec<String> {
+    let target = targets.get(id);
+    let scope = target.scope(env);
+    target.actions.iter()
+        .map(|inv| format!("{}\n{}", inv.action.name, command(targets, &scope, inv, &inv.sources, None).text))
+        .collect()
+}
+
+fn filter_sources(targets: &Targets, action: &Action, sources: &List) -> List {
+    let mut filtered = List::new();
+    for s in sources.iter() {
+        let keep = match targets.find(s).map(|id| targets.get(id)) {
+            Some(t) => (!action.has(Flags::Updated) || t.fate.needs_build())
+                && (!action.has(Flags::Existing) || t.time.is_some()),
+            None => !action.has(Flags::Updated) && !action.has(Flags::Existing),
+        };
+        if keep {
+            filtered.append(s);
+        }
+    }
+    filtered
+}
+
+fn bound(targets: &Targets, names: &List) -> List {
+    let mut l = List::new();
+    for n in names.iter() {
+        match targets.find(n) {
+            Some(id) => l.append(targets.get(id).bound_name()),
+            None => l.append(n),
+        }
+    }
+    l
+}
+
+/// A response file name unique to this run.
+fn response_path() -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let n = COUNT.fetch_add(1, Ordering::Relaxed);
+    env::temp_dir().join(format!("jam{}-{}.rsp", process::id(), n))
+}
+
+/// Writes `s` as one response file argument, quoted if need be.
+fn response_word(s: &str) -> String {
+    if s.chars().any(|c| c.is_whitespace() || c == '"' || c == '\\') {
+        format!("\"{}\"", s.replace('\\', "\\\\").replace('"', "\\\""))
+    } else {
+        s.to_string()
+    }
+}
+
+/// Expands the command of `inv` with `sources`, or with a response file
+/// holding them named after `response` if that is given.
+fn command(targets: &Targets, scope: &Environment, inv: &Invocation, sources: &List,
+           response: Option<&str>) -> Command {
+    let bound_targets = bound(targets, &inv.targets);
+    let bound_sources = bound(targets, sources);
+    let response = response.map(|prefix| {
+        let path = response_path();
+        let words: Vec<String> = bound_sources.iter().map(|s| response_word(s)).collect();
+        let arg = format!("{}{}", prefix, path.display());
+        (Response { path, contents: words.join("\n") + "\n" }, List::from(vec![arg]))
+    });
+    let passed = response.as_ref().map_or(&bound_sources, |r| &r.1);
+    let mut locals = HashMap::new();
+    for &(name, value) in &[("<", &bound_targets), ("1", &bound_targets), (">", passed), ("2", passed)] {
+        locals.insert(name.to_string(), Variable::new(value.clone()));
+    }
+    for var in inv.action.bind_list.iter() {
+        let value = locals.get(var).or_else(|| scope.get(var)).map(|v| bound(targets, v.value()));
+        if let Some(value) = value {
+            locals.insert(var.to_string(), Variable::new(value));
+        }
+    }
+    let env = Environment::new_with_settings(scope, &locals);
+
+    Command {
+        action: inv.action.clone(),
+        target: inv.targets.get(0).unwrap_or("").to_string(),
+        targets: bound_targets,
+        sources: bound_sources,
+        text: expand::expand_string(&inv.action.command, &env),
+        response: response.map(|r| r.0),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use testing::TempDir;
+    use variable::Assign;
+
+    #[test]
+    fn together_merges_sources() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let ar = Rc::new(Action::new("Archive", vec![Flags::Together], "ar ru $(<) $(>)"));
+        ts.invoke(&ar, &List::from(vec!["lib.a"]), &List::from(vec!["a.o"]));
+        ts.invoke(&ar, &List::from(vec!["lib.a"]), &List::from(vec!["b.o", "a.o"]));
+
+        let id = ts.find("lib.a").unwrap();
+        let cmds = build(&ts, &env, id, &mut HashSet::new()).unwrap();
+        assert_eq!(cmds.len(), 1);
+        assert_eq!(cmds[0].text, "ar ru lib.a a.o b.o");
+        assert_eq!(cmds[0].target, "lib.a");
+    }
+
+    #[test]
+    fn piecemeal_splits_long_commands() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let rm = Rc::new(Action::new("Clean", vec![Flags::Piecemeal, Flags::Maxline(20)], "rm $(>)"));
+        ts.invoke(&rm, &List::from(vec!["clean"]), &List::from(vec!["aaaa", "bbbb", "cccc", "dddd", "eeee"]));
+
+        let id = ts.find("clean").unwrap();
+        let cmds = build(&ts, &env, id, &mut HashSet::new()).unwrap();
+        let texts: Vec<&str> = cmds.iter().map(|c| c.text.as_str()).collect();
+        assert_eq!(texts, vec!["rm aaaa bbbb cccc", "rm dddd eeee"]);
+    }
+
+    #[test]
+    fn long_commands_are_errors_without_piecemeal() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let rm = Rc::new(Action::new("Clean", vec![Flags::Maxline(10)], "rm $(>)"));
+        ts.invoke(&rm, &List::from(vec!["clean"]), &List::from(vec!["aaaa", "bbbb", "cccc"]));
+
+        let id = ts.find("clean").unwrap();
+        assert!(build(&ts, &env, id, &mut HashSet::new()).is_err());
+    }
+
+    #[test]
+    fn response_files_for_long_commands() {
+        let mut env = Environment::new();
+        env.set("JAMRESPONSE", Assign::Set, &List::from(vec!["@"]));
+        let mut ts = Targets::new();
+        let link = Rc::new(Action::new("Link", vec![Flags::Maxline(60)], "ld -o $(<) $(>)"));
+        let objects = List::from(vec!["aaaaaaaaaaaaaaaaaaaa.o", "bbbbbbbbbbbbbbbbbbbb.o", "c c.o"]);
+        ts.invoke(&link, &List::from(vec!["prog"]), &objects);
+
+        let id = ts.find("prog").unwrap();
+        let cmds = build(&ts, &env, id, &mut HashSet::new()).unwrap();
+        let response = cmds[0].response.as_ref().unwrap();
+        assert_eq!(cmds[0].text, format!("ld -o prog @{}", response.path.display()));
+        assert_eq!(response.contents, "aaaaaaaaaaaaaaaaaaaa.o\nbbbbbbbbbbbbbbbbbbbb.o\n\"c c.o\"\n");
+        assert_eq!(cmds[0].sources.length(), 3);
+
+        let short = Rc::new(Action::new("Link", vec![], "ld -o $(<) $(>)"));
+        ts.invoke(&short, &List::from(vec!["short"]), &List::from(vec!["a.o"]));
+        let id = ts.find("short").unwrap();
+        assert_eq!(build(&ts, &env, id, &mut HashSet::new()).unwrap()[0].response, None);
+    }
+
+    #[test]
+    fn response_files_never_written_through() {
+        let dir = TempDir::new("response-create-test");
+        let taken = dir.join("taken.rsp");
+        fs::write(&taken, "theirs").unwrap();
+        let mut cmd = Command {
+            action: Rc::new(Action::new("Link", vec![], "")),
+            target: "prog".to_string(),
+            targets: List::from(vec!["prog"]),
+            sources: List::new(),
+            text: format!("ld @{}", taken.display()),
+            response: Some(Response { path: taken.clone(), contents: "a.o\n".to_string() }),
+        };
+        cmd.write_response().unwrap();
+
+        let path = cmd.response.as_ref().unwrap().path.clone();
+        assert_ne!(path, taken);
+        assert_eq!(cmd.text, format!("ld @{}", path.display()));
+        assert_eq!(fs::read_to_string(&taken).unwrap(), "theirs");
+        assert_eq!(fs::read_to_string(&path).unwrap(), "a.o\n");
+        assert_eq!(fs::metadata(&path).unwrap().permissions().mode() & 0o777, 0o600);
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn shared_invocations_run_once() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let yacc = Rc::new(Action::new("Yacc", vec![], "yacc $(>)"));
+        ts.invoke(&yacc, &List::from(vec!["y.tab.c", "y.tab.h"]), &List::from(vec!["gram.y"]));
+
+        let mut done = HashSet::new();
+        let c = ts.find("y.tab.c").unwrap();
+        let h = ts.find("y.tab.h").unwrap();
+        assert_eq!(build(&ts, &env, c, &mut done).unwrap().len(), 1);
+        assert_eq!(build(&ts, &env, h, &mut done).unwrap().len(), 0);
+    }
+}
@@ -0,0 +1,88 @@
+use std::collections::HashSet;
+use std::env;
+use std::io::{self, Write};
+use command;
+use env::Environment;
+use graph::Targets;
+use jam_depgraph::NodeId;
+use json;
+use list::List;
+
+/// The Jambase actions that compile C and C++ sources.
+pub const COMPILE_ACTIONS: &[&str] = &["Cc", "C++"];
+
+/// Writes a clang compilation database for the `Cc` and `C++` actions of
+/// every target reachable from the bound `roots`, one entry per source.
+/// Nothing is run.
+pub fn write(targets: &Targets, env: &Environment, roots: &List, out: &mut dyn Write) -> io::Result<()> {
+    let directory = env::current_dir()?.to_string_lossy().into_owned();
+    let roots: Vec<NodeId> = roots.iter().filter_map(|n| targets.find(n)).collect();
+    let mut done = HashSet::new();
+    let mut entries = Vec::new();
+
+    for id in targets.graph().reachable(&roots) {
+        let compiles = targets.get(id).actions.iter()
+            .any(|inv| COMPILE_ACTIONS.contains(&inv.action.name.as_str()));
+        if !compiles {
+            continue;
+        }
+        let commands = command::build(targets, env, id, &mut done)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e.to_string()))?;
+        for cmd in commands {
+            if !COMPILE_ACTIONS.contains(&cmd.action.name.as_str()) {
+                continue;
+            }
+            let text: Vec<&str> = cmd.text.lines().map(|l| l.trim()).filter(|l| !l.is_empty()).collect();
+            for source in cmd.sources.iter() {
+                entries.push(format!("  {{\"directory\": {}, \"command\": {}, \"file\": {}, \"output\": {}}}",
+                                     json::string(&directory),
+                                     json::string(&text.join(" ")),
+                                     json::string(source),
+                                     json::string(cmd.targets.get(0).unwrap_or(""))));
+            }
+        }
+    }
+
+    if entries.is_empty() {
+        return writeln!(out, "[]");
+    }
+    writeln!(out, "[\n{}\n]", entries.join(",\n"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::Action;
+    use jam_depgraph::Edge;
+    use std::rc::Rc;
+    use variable::Assign;
+
+    #[test]
+    fn one_entry_per_compiled_source() {
+        let mut env = Environment::new();
+        env.set("CCFLAGS", Assign::Set, &List::from(vec!["-O"]));
+        let cc = Rc::new(Action::new("Cc", vec![], "\n\tcc -c -o $(<) $(CCFLAGS) $(>)\n"));
+        let link = Rc::new(Action::new("Link", vec![], "cc -o $(<) $(>)"));
+
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["a.o", "b.o"]), Edge::Depends);
+        for &(obj, src) in &[("a.o", "a.c"), ("b.o", "b.c")] {
+            ts.invoke(&cc, &List::from(vec![obj]), &List::from(vec![src]));
+        }
+        ts.set_on(&List::from(vec!["b.o"]), "CCFLAGS", Assign::Append, &List::from(vec!["-g"]));
+        ts.invoke(&link, &List::from(vec!["prog"]), &List::from(vec!["a.o", "b.o"]));
+
+        let mut out = Vec::new();
+        write(&ts, &env, &List::from(vec!["prog"]), &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        let dir = json::string(&env::current_dir().unwrap().to_string_lossy());
+        assert_eq!(out, format!(
+            "[\n  {{\"directory\": {0}, \"command\": \"cc -c -o a.o -O a.c\", \"file\": \"a.c\", \"output\": \"a.o\"}},\
+             \n  {{\"directory\": {0}, \"command\": \"cc -c -o b.o -g b.c\", \"file\": \"b.c\", \"output\": \"b.o\"}}\
+             \n]\n", dir));
+
+        let mut out = Vec::new();
+        write(&ts, &env, &List::from(vec!["a.c"]), &mut out).unwrap();
+        assert_eq!(out, b"[]\n");
+    }
+}
@@ -0,0 +1,179 @@
+//! Debug output levels, as selected with `-d`.
+//!
+//! The numbered levels follow `Jam.html`; the display options `-da`,
+//! `-dc`, `-dd`, `-dm` and `-dx` map onto levels of their own above 10.
+//! The levels in force are kept per thread and set with `install`, so any
+//! part of jam can check them or emit output with the `trace!` macro.
+
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Write};
+
+/// Show actions and summary info (the default).
+pub const MAKE: usize = 1;
+/// Show the dependency analysis, timestamps and bound paths (`-dm`).
+pub const MAKEPROG: usize = 3;
+/// Show how commands are run.
+pub const EXECCMD: usize = 4;
+/// Show rule invocations and variable expansions.
+pub const COMPILE: usize = 5;
+/// Show directory, header file and archive scans, and binding searches.
+pub const SEARCH: usize = 6;
+/// Show variable settings.
+pub const VARSET: usize = 7;
+/// Show variable fetches.
+pub const VARGET: usize = 8;
+/// Show how often rules were called and how long they took.
+pub const PROFILE: usize = 10;
+/// Show quiet actions too (`-da`).
+pub const MAKEQ: usize = 11;
+/// Show the text of actions (`-dx`).
+pub const EXEC: usize = 12;
+/// Show the dependency graph (`-dd`).
+pub const DEPENDS: usize = 13;
+/// Show the targets that cause rebuilds (`-dc`).
+pub const CAUSES: usize = 14;
+
+const MAX: usize = 15;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Levels([bool; MAX]);
+
+impl Default for Levels {
+    fn default() -> Levels {
+        let mut l = Levels::none();
+        l.set(MAKE);
+        l
+    }
+}
+
+impl Levels {
+    pub fn none() -> Levels { Levels([false; MAX]) }
+
+    pub fn enabled(&self, level: usize) -> bool {
+        level < MAX && self.0[level]
+    }
+
+    pub fn set(&mut self, level: usize) {
+        if level < MAX {
+            self.0[level] = true;
+        }
+    }
+
+    /// Applies the argument of a `-d` option: `n` enables levels 1 to `n`,
+    /// `+n` enables level `n` alone, `0` turns everything off, and letters
+    /// turn off the default display in favour of the options named.
+    pub fn apply(&mut self, spec: &str) -> Result<(), String> {
+        let bad = || format!("invalid debug level -d{}", spec);
+        if let Some(n) = spec.strip_prefix('+') {
+            let n = n.parse::<usize>().map_err(|_| bad())?;
+            self.set(n);
+        } else if spec.chars().all(|c| c.is_ascii_digit()) && !spec.is_empty() {
+            let n = spec.parse::<usize>().map_err(|_| bad())?;
+            if n == 0 {
+                *self = Levels::none();
+            }
+            for level in 1..(n + 1).min(MAX) {
+                self.set(level);
+            }
+        } else {
+            self.0[MAKE] = false;
+            for c in spec.chars() {
+                match c {
+                    'a' => { self.set(MAKE); self.set(MAKEQ); }
+                    'c' => self.set(CAUSES),
+                    'd' => self.set(DEPENDS),
+                    'm' => self.set(MAKEPROG),
+                    'x' => self.set(EXEC),
+                    _ => return Err(bad()),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static LEVELS: Cell<Levels> = Cell::new(Levels::default());
+    static CAPTURE: RefCell<Option<Vec<u8>>> = const { RefCell::new(None) };
+}
+
+/// Makes `levels` the ones in force on this thread.
+pub fn install(levels: Levels) {
+    LEVELS.with(|l| l.set(levels));
+}
+
+pub fn enabled(level: usize) -> bool {
+    LEVELS.with(|l| l.get().enabled(level))
+}
+
+/// Writes debug output, to stdout unless it is being captured.
+pub fn write(args: fmt::Arguments) {
+    CAPTURE.with(|c| match *c.borrow_mut() {
+        Some(ref mut buf) => { let _ = buf.write_fmt(args); }
+        None => { let _ = io::stdout().write_fmt(args); }
+    });
+}
+
+/// Runs `f` with `levels` installed and returns the debug output it wrote.
+#[cfg(test)]
+pub fn capture<F: FnOnce()>(levels: Levels, f: F) -> String {
+    let previous = LEVELS.with(|l| l.replace(levels));
+    CAPTURE.with(|c| *c.borrow_mut() = Some(Vec::new()));
+    f();
+    let out = CAPTURE.with(|c| c.borrow_mut().take()).unwrap_or_default();
+    install(previous);
+    String::from_utf8_lossy(&out).into_owned()
+}
+
+/// Writes a line of debug output if `level` is enabled.
+macro_rules! trace {
+    ($level:expr, $($arg:tt)*) => {
+        if ::debug::enabled($level) {
+            ::debug::write(format_args!("{}\n", format_args!($($arg)*)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn levels(spec: &str) -> Levels {
+        let mut l = Levels::default();
+        l.apply(spec).unwrap();
+        l
+    }
+
+    #[test]
+    fn numbered_levels() {
+        let l = levels("5");
+        assert!((1..6).all(|n| l.enabled(n)));
+        assert!(!l.enabled(6));
+
+        let l = levels("+7");
+        assert!(l.enabled(MAKE) && l.enabled(VARSET) && !l.enabled(COMPILE));
+
+        assert_eq!(levels("0"), Levels::none());
+        assert!(Levels::default().apply("+x").is_err());
+    }
+
+    #[test]
+    fn display_options() {
+        let l = levels("x");
+        assert!(l.enabled(EXEC) && !l.enabled(MAKE));
+        let l = levels("ac");
+        assert!(l.enabled(MAKE) && l.enabled(MAKEQ) && l.enabled(CAUSES));
+        assert!(Levels::default().apply("q").is_err());
+    }
+
+    #[test]
+    fn capturing_output() {
+        let out = capture(levels("+7"), || {
+            trace!(VARSET, "set {} = {}", "X", "a");
+            trace!(VARGET, "get {}", "X");
+        });
+        assert_eq!(out, "set X = a\n");
+        assert!(!enabled(VARSET));
+    }
+}
@@ -1,8 +1,10 @@
 use std::collections::HashMap;
+use debug;
 use std::ops::{Index, IndexMut};
-use variable::Variable;
+use list::List;
+use variable::{Assign, Variable};
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Default, PartialEq)]
 pub struct Environment<'a> {
     parent: Option<&'a Environment<'a>>,
     vars: HashMap<String, Variable>
@@ -23,22 +25,121 @@ impl<'a> Environment<'a> {
         }
     }
 
+    /// Creates a child scope of `env` in which `settings` shadow the
+    /// parent's values, as done for target-specific variables.
+    pub fn new_with_settings(env: &'a Environment, settings: &HashMap<String, Variable>) -> Environment<'a> {
+        Environment {
+            parent: Some(env),
+            vars: settings.clone()
+        }
+    }
+
     pub fn parent(&'a self) -> Option<&'a Environment<'a>> {
         self.parent
     }
+
+    pub fn get(&self, name: &str) -> Option<&Variable> {
+        let v = self.lookup(name);
+        trace!(debug::VARGET, "get {} = {}", name, v.map(|v| v.value().to_string()).unwrap_or_default());
+        v
+    }
+
+    fn lookup(&self, name: &str) -> Option<&Variable> {
+        match self.vars.get(name) {
+            Some(v) => Some(v),
+            None => self.parent.and_then(|p| p.lookup(name))
+        }
+    }
+
+    /// Assigns `value` to `name`.  The assignment lasts unless `name` was
+    /// given its value by `push`, in which case `pop` puts back the old one.
+    /// In a view made by `new_with_settings`, whose parent is borrowed, a
+    /// value inherited from the parent is copied in first so that `+=` and
+    /// `?=` see it.
+    pub fn set(&mut self, name: &str, op: Assign, value: &List) {
+        trace!(debug::VARSET, "set {} {} {}", name, op, value);
+        if !self.vars.contains_key(name) {
+            let inherited = self.parent.and_then(|p| p.lookup(name)).cloned();
+            if let Some(v) = inherited {
+                if op == Assign::Default {
+                    return;
+                }
+                self.vars.insert(name.to_string(), v);
+            }
+        }
+        if let Some(v) = op.apply(self.vars.get_mut(name), value) {
+            self.vars.insert(name.to_string(), v);
+        }
+    }
+
+    /// Defines a variable for each of `vars`, normally the process
+    /// environment.  As in jam, a value whose name ends in `PATH` is split
+    /// into a list at each `:`; any other is a single word.
+    pub fn import<I, K, V>(&mut self, vars: I)
+        where I: IntoIterator<Item = (K, V)>, K: AsRef<str>, V: AsRef<str>
+    {
+        for (name, value) in vars {
+            let (name, value) = (name.as_ref(), value.as_ref());
+            let value = if name.ends_with("PATH") {
+                List::from(value.split(':').collect::<Vec<_>>())
+            } else {
+                List::from(vec![value])
+            };
+            self.set(name, Assign::Set, &value);
+        }
+    }
+
+    /// Gives the variables in `settings` their values until `pop`, as jam's
+    /// `pushsettings` does for target-specific variables and `local` does
+    /// for a block.  Assignments to any other variable are unaffected.
+    pub fn push(&mut self, settings: &HashMap<String, Variable>) -> Saved {
+        Saved(settings.iter().map(|(name, v)| {
+            trace!(debug::VARSET, "push {} = {}", name, v.value());
+            (name.clone(), self.vars.insert(name.clone(), v.clone()))
+        }).collect())
+    }
+
+    /// Puts back the values `push` replaced, returning the ones the pushed
+    /// variables ended up with, which a target keeps as its settings.
+    pub fn pop(&mut self, saved: Saved) -> HashMap<String, Variable> {
+        let mut last = HashMap::new();
+        for (name, old) in saved.0 {
+            trace!(debug::VARSET, "pop {}", name);
+            let current = match old {
+                Some(v) => self.vars.insert(name.clone(), v),
+                None => self.vars.remove(&name),
+            };
+            if let Some(v) = current {
+                last.insert(name, v);
+            }
+        }
+        last
+    }
 }
 
+/// The values of the variables given new ones by `Environment::push`.
+#[derive(Debug)]
+pub struct Saved(Vec<(String, Option<Variable>)>);
+
+/// What an unset variable expands to.
+static EMPTY: Variable = Variable::new(List::new());
+
 impl<'a, T> Index<T> for Environment<'a> where T: ToString {
     type Output = Variable;
 
     fn index(&self, idx: T) -> &Self::Output {
-        unimplemented!();
+        self.get(&idx.to_string()).unwrap_or(&EMPTY)
     }
 }
 
 impl<'a, T> IndexMut<T> for Environment<'a> where T: ToString {
     fn index_mut(&mut self, idx: T) -> &mut Self::Output {
-        unimplemented!();
+        let name = idx.to_string();
+        if !self.vars.contains_key(&name) {
+            let v = self.lookup(&name).cloned().unwrap_or_else(|| Variable::new(List::new()));
+            self.vars.insert(name.clone(), v);
+        }
+        self.vars.get_mut(&name).unwrap()
     }
 }
 
@@ -59,4 +160,63 @@ mod tests {
         assert!(c.parent().is_some());
         assert_eq!(p, *c.parent().unwrap());
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_assignment_operators() {
+        let mut e = Environment::new();
+        e.set("X", Assign::Default, &List::from(vec!["a"]));
+        e.set("X", Assign::Default, &List::from(vec!["b"]));
+        assert_eq!(*e["X"].value(), List::from(vec!["a"]));
+        e.set("X", Assign::Append, &List::from(vec!["c"]));
+        assert_eq!(*e["X"].value(), List::from(vec!["a", "c"]));
+        e.set("X", Assign::Set, &List::from(vec!["d"]));
+        assert_eq!(*e["X"].value(), List::from(vec!["d"]));
+    }
+
+    #[test]
+    fn test_undefined_variable_is_empty() {
+        let e = Environment::new();
+        assert_eq!(*e["UNSET"].value(), List::new());
+    }
+
+    #[test]
+    fn test_import_splits_paths() {
+        let mut e = Environment::new();
+        e.import(vec![("PATH", "/bin:/usr/bin"), ("CC", "gcc -m32")]);
+        assert_eq!(*e["PATH"].value(), List::from(vec!["/bin", "/usr/bin"]));
+        assert_eq!(*e["CC"].value(), List::from(vec!["gcc -m32"]));
+    }
+
+    #[test]
+    fn test_push_restores_only_pushed_variables() {
+        let mut e = Environment::new();
+        e.set("CCFLAGS", Assign::Set, &List::from(vec!["-O"]));
+        let mut settings = HashMap::new();
+        settings.insert("CCFLAGS".to_string(), Variable::new(List::from(vec!["-g"])));
+        settings.insert("LOCATE".to_string(), Variable::new(List::from(vec!["obj"])));
+        let saved = e.push(&settings);
+        e.set("CCFLAGS", Assign::Append, &List::from(vec!["-Wall"]));
+        e.set("LIBS", Assign::Set, &List::from(vec!["-lm"]));
+        assert_eq!(*e["CCFLAGS"].value(), List::from(vec!["-g", "-Wall"]));
+
+        let last = e.pop(saved);
+        assert_eq!(*last["CCFLAGS"].value(), List::from(vec!["-g", "-Wall"]));
+        assert_eq!(*last["LOCATE"].value(), List::from(vec!["obj"]));
+        assert_eq!(*e["CCFLAGS"].value(), List::from(vec!["-O"]));
+        assert!(e.get("LOCATE").is_none());
+        assert_eq!(*e["LIBS"].value(), List::from(vec!["-lm"]));
+    }
+
+    #[test]
+    fn test_child_shadows_parent() {
+        let mut p = Environment::new();
+        p.set("X", Assign::Set, &List::from(vec!["a"]));
+        {
+            let mut c = Environment::new_with_parent(&p);
+            c.set("X", Assign::Append, &List::from(vec!["b"]));
+            assert_eq!(*c["X"].value(), List::from(vec!["a", "b"]));
+        }
+        assert_eq!(*p["X"].value(), List::from(vec!["a"]));
+        assert!(p.get("Y").is_none());
+    }
+}
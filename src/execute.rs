@@ -0,0 +1,1050 @@
+use std::collections::{HashMap, HashSet};
+use std::fs;
+use std::io::{self, IsTerminal, Read, Write};
+use std::path::Path;
+use std::process;
+use std::rc::Rc;
+use std::sync::atomic::AtomicBool;
+use std::thread;
+use std::time::{Duration, Instant};
+use cache::{self, CacheStore};
+use command::{self, Command};
+use debug;
+use env::Environment;
+use graph::Targets;
+use hash::Digest;
+use interrupt;
+use jam_depgraph::NodeId;
+use jobserver::{Jobserver, Slot};
+use list::List;
+use make;
+use options::Options;
+use profile;
+use schedule::{Declared, NewestFirst, Priority};
+use script::ScriptWriter;
+use state::{self, State};
+use target::{Fate, Flags};
+
+/// Counts of what happened to the targets during a run, and how long it
+/// and each command run took.
+#[derive(Debug, Default, PartialEq)]
+pub struct Stats {
+    pub updated: usize,
+    pub failed: usize,
+    pub skipped: usize,
+    /// Targets that do not exist and have no actions to make them.
+    pub cantfind: usize,
+    /// Targets with actions that depend on ones that cannot be found.
+    pub cantmake: usize,
+    pub elapsed: Duration,
+    pub timings: Vec<Timing>,
+    /// Whether the run was cut short by a signal.
+    pub interrupted: bool,
+}
+
+/// The wall time one command took.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Timing {
+    pub action: String,
+    pub target: String,
+    pub time: Duration,
+    pub ok: bool,
+}
+
+/// The output of an action that failed, shown again at the end of the run.
+#[derive(Debug)]
+struct Failure {
+    header: String,
+    text: String,
+    output: Vec<u8>,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Status {
+    Ok,
+    Failed,
+}
+
+/// A target walked but not yet made.
+struct Pending {
+    /// How many of its dependencies are still to be made, and one more
+    /// while the walk is below it.
+    left: usize,
+    /// The first dependency that failed.
+    failed: Option<NodeId>,
+    /// The targets depending on it that wait for it to be made.
+    waiters: Vec<NodeId>,
+    /// When the walk was done with it, or nothing while it is below it.
+    rank: Option<usize>,
+}
+
+/// The commands of a target being updated.
+struct Update<'a> {
+    id: NodeId,
+    key: Option<Digest>,
+    /// The commands still to run after the one running now.
+    commands: ::std::vec::IntoIter<Command>,
+    /// The jobserver's slot the commands run in, held until the last is
+    /// done.
+    _slot: Option<Slot<'a>>,
+}
+
+/// A command running for an update.
+struct Job<'a> {
+    update: Update<'a>,
+    cmd: Command,
+    header: String,
+    /// What is printed before the command's output.
+    block: Vec<u8>,
+    process: Process,
+    start: Instant,
+    /// Which of the `-j` job slots the command runs in, for the profile.
+    lane: usize,
+}
+
+/// Walks the bound dependency graph from `roots`, updating each target
+/// whose fate calls for it after its dependencies, as jam's `make1` does.
+/// With `-j` up to that many commands run at once, the targets ready to
+/// be updated starting in the order the priority gives them, or else in
+/// the order the walk reached them.  Under `-n`
+/// the commands are printed but not run, and with a script writer they
+/// are written to the script instead.
+///
+/// The output of each command is captured and printed in one piece with
+/// its header, so that commands never interleave their output, and with
+/// `--log-dir` each target's is also kept in a log file of its own.
+///
+/// A target that does not exist and has no actions fails, as do the
+/// targets depending on it.  A target whose dependencies failed is
+/// skipped, and with `-q` or `--keep-going=n` the run stops after that
+/// many failures.  The targets of a command that fails are removed,
+/// unless `PRECIOUS` or `--keep-failed` is given, and `TEMPORARY` targets
+/// built on the way are removed once every target depending on them has
+/// been updated.  Once interrupted, the commands running are interrupted
+/// too, their targets are removed in the same way, and nothing else runs.
+pub struct Executor<'a, 'e: 'a> {
+    targets: &'a Targets,
+    env: &'a Environment<'e>,
+    options: &'a Options,
+    out: &'a mut dyn Write,
+    script: Option<ScriptWriter<'a>>,
+    state: Option<&'a mut State>,
+    cache: Option<&'a dyn CacheStore>,
+    interrupt: &'a AtomicBool,
+    jobserver: Option<&'a Jobserver>,
+    priority: Box<dyn Priority>,
+    /// How many commands may run at once.
+    jobs: usize,
+    status: HashMap<NodeId, Status>,
+    pending: HashMap<NodeId, Pending>,
+    /// How many targets the walk is done with.
+    walked: usize,
+    /// The targets ready to be updated, by when the walk was done with
+    /// them.
+    queue: Vec<(usize, NodeId)>,
+    running: Vec<Job<'a>>,
+    done: HashSet<*const ::action::Invocation>,
+    logged: HashSet<String>,
+    failures: Vec<Failure>,
+    /// For each target, how many of the targets depending on it are still
+    /// to be made.
+    parents: HashMap<NodeId, usize>,
+    /// The targets updated during the run.
+    built: HashSet<NodeId>,
+    /// Temporary targets a dependent of which failed, and so are kept.
+    kept: HashSet<NodeId>,
+    stats: Stats,
+}
+
+impl<'a, 'e> Executor<'a, 'e> {
+    pub fn new(targets: &'a Targets, env: &'a Environment<'e>, options: &'a Options,
+               out: &'a mut dyn Write) -> Executor<'a, 'e> {
+        Executor {
+            targets,
+            env,
+            options,
+            out,
+            script: None,
+            state: None,
+            cache: None,
+            interrupt: interrupt::flag(),
+            jobserver: None,
+            priority: match options.newest_first {
+                true => Box::new(NewestFirst::new()),
+                false => Box::new(Declared),
+            },
+            jobs: options.jobs.unwrap_or(1),
+            status: HashMap::new(),
+            pending: HashMap::new(),
+            walked: 0,
+            queue: Vec::new(),
+            running: Vec::new(),
+            done: HashSet::new(),
+            logged: HashSet::new(),
+            failures: Vec::new(),
+            parents: HashMap::new(),
+            built: HashSet::new(),
+            kept: HashSet::new(),
+            stats: Stats::default(),
+        }
+    }
+
+    pub fn script(mut self, script: ScriptWriter<'a>) -> Executor<'a, 'e> {
+        self.script = Some(script);
+        self
+    }
+
+    /// Records the actions, and with `--content-hash` the inputs, of each
+    /// target brought up to date in `state`.
+    pub fn state(mut self, state: &'a mut State) -> Executor<'a, 'e> {
+        self.state = Some(state);
+        self
+    }
+
+    /// Restores targets from `cache` instead of running their actions
+    /// when it has them, and stores the ones it has not.
+    pub fn cache(mut self, cache: &'a dyn CacheStore) -> Executor<'a, 'e> {
+        self.cache = Some(cache);
+        self
+    }
+
+    /// Watches `flag` instead of the one set by the signal handler.
+    pub fn interrupt(mut self, flag: &'a AtomicBool) -> Executor<'a, 'e> {
+        self.interrupt = flag;
+        self
+    }
+
+    /// Runs each update in a job slot taken from `jobserver`, as shared
+    /// with the make or Cargo that started jam.  Without `-j`, as many
+    /// run at once as there are slots to be had.
+    pub fn jobserver(mut self, jobserver: &'a Jobserver) -> Executor<'a, 'e> {
+        self.jobserver = Some(jobserver);
+        self.jobs = self.options.jobs.unwrap_or(usize::MAX);
+        self
+    }
+
+    /// Walks the dependencies of each target, and starts the targets ready
+    /// to be updated, in the order `priority` gives them, instead of
+    /// declaration order or `-g`'s.
+    pub fn priority<P: Priority + 'static>(mut self, priority: P) -> Executor<'a, 'e> {
+        self.priority = Box::new(priority);
+        self
+    }
+
+    /// Updates `roots`, then prints the output of the commands that failed
+    /// and jam's summary of the run.
+    pub fn run(mut self, roots: &List) -> io::Result<Stats> {
+        let start = Instant::now();
+        let roots: Vec<NodeId> = roots.iter().filter_map(|n| self.targets.find(n)).collect();
+        self.count_parents(&roots);
+        for &id in &roots {
+            self.make1(id, None)?;
+        }
+        loop {
+            self.dispatch()?;
+            if self.running.is_empty() {
+                break;
+            }
+            self.wait()?;
+        }
+        if !self.failures.is_empty() {
+            writeln!(self.out, "...output of {} failed action(s)...", self.failures.len())?;
+            for f in &self.failures {
+                writeln!(self.out, "{}\n{}", f.header, f.text.trim_matches('\n'))?;
+                self.out.write_all(&f.output)?;
+            }
+        }
+        if self.options.debug.enabled(debug::MAKE) {
+            if self.stats.cantfind > 0 {
+                writeln!(self.out, "...can't find {} target(s)...", self.stats.cantfind)?;
+            }
+            if self.stats.cantmake > 0 {
+                writeln!(self.out, "...can't make {} target(s)...", self.stats.cantmake)?;
+            }
+            if self.stats.failed > 0 {
+                writeln!(self.out, "...failed updating {} target(s)...", self.stats.failed)?;
+            }
+            if self.stats.skipped > 0 {
+                writeln!(self.out, "...skipped {} target(s)...", self.stats.skipped)?;
+            }
+            if self.stats.updated > 0 {
+                writeln!(self.out, "...updated {} target(s)...", self.stats.updated)?;
+            }
+        }
+        self.stats.elapsed = start.elapsed();
+        self.stats.interrupted = interrupt::interrupted(self.interrupt);
+        Ok(self.stats)
+    }
+
+    /// Walks target `id` and its dependencies for `parent`, ordering the
+    /// dependencies of each target by the priority.  A target is handled
+    /// once every dependency it has is made, which for the ones still
+    /// waiting for commands happens later, as the commands finish.
+    fn make1(&mut self, id: NodeId, parent: Option<NodeId>) -> io::Result<()> {
+        if let Some(&status) = self.status.get(&id) {
+            if let (Some(parent), Status::Failed) = (parent, status) {
+                self.pending.get_mut(&parent).unwrap().failed.get_or_insert(id);
+            }
+            return Ok(());
+        }
+        if let Some(p) = self.pending.get_mut(&id) {
+            // A target still being walked depends on itself, and the cycle
+            // is broken here.
+            if let (Some(parent), Some(_)) = (parent, p.rank) {
+                p.waiters.push(parent);
+                self.pending.get_mut(&parent).unwrap().left += 1;
+            }
+            return Ok(());
+        }
+        if self.stopped() {
+            if let Some(parent) = parent {
+                self.pending.get_mut(&parent).unwrap().failed.get_or_insert(id);
+            }
+            return Ok(());
+        }
+        self.pending.insert(id, Pending { left: 1, failed: None, waiters: parent.into_iter().collect(), rank: None });
+        if let Some(parent) = parent {
+            self.pending.get_mut(&parent).unwrap().left += 1;
+        }
+
+        let mut deps = make::dependencies(self.targets, id);
+        self.priority.order(self.targets, &mut deps);
+        for d in deps {
+            self.make1(d, Some(id))?;
+        }
+        self.pending.get_mut(&id).unwrap().rank = Some(self.walked);
+        self.walked += 1;
+        self.settle(id, None)
+    }
+
+    /// Notes that dependency `dep` of target `id` is made, or with nothing
+    /// that the walk below the target is over, handling the target if that
+    /// was the last thing it waited for.
+    fn settle(&mut self, id: NodeId, dep: Option<(NodeId, Status)>) -> io::Result<()> {
+        let p = self.pending.get_mut(&id).unwrap();
+        if let Some((d, Status::Failed)) = dep {
+            p.failed.get_or_insert(d);
+        }
+        p.left -= 1;
+        if p.left > 0 {
+            return Ok(());
+        }
+
+        let (failed_dep, rank) = (p.failed, p.rank.unwrap_or_default());
+        let target = self.targets.get(id);
+        let status = if target.fate == Fate::CantFind {
+            writeln!(self.out, "don't know how to make {}", target.name())?;
+            self.stats.cantfind += 1;
+            Status::Failed
+        } else if target.fate == Fate::CantMake {
+            if !target.actions.is_empty() {
+                self.stats.cantmake += 1;
+                if let Some(d) = failed_dep {
+                    writeln!(self.out, "...skipped {} for lack of {}...",
+                             target.name(), self.targets.get(d).name())?;
+                    self.stats.skipped += 1;
+                }
+            }
+            Status::Failed
+        } else if !target.fate.needs_build() || target.actions.is_empty() {
+            let unrecorded = self.state.as_ref().is_some_and(|s| s.get(target.name()).is_none());
+            if target.fate == Fate::Stable && !target.actions.is_empty() && unrecorded {
+                self.record(id);
+            }
+            if failed_dep.is_some() { Status::Failed } else { Status::Ok }
+        } else if let Some(d) = failed_dep {
+            writeln!(self.out, "...skipped {} for lack of {}...",
+                     target.name(), self.targets.get(d).name())?;
+            self.stats.skipped += 1;
+            Status::Failed
+        } else {
+            self.queue.push((rank, id));
+            return Ok(());
+        };
+        self.finish(id, status)
+    }
+
+    /// Records that target `id` is made, passing that on to the targets
+    /// waiting for it.
+    fn finish(&mut self, id: NodeId, status: Status) -> io::Result<()> {
+        self.status.insert(id, status);
+        self.made(id, status)?;
+        let p = self.pending.remove(&id).unwrap();
+        for w in p.waiters {
+            self.settle(w, Some((id, status)))?;
+        }
+        Ok(())
+    }
+
+    /// Starts updating the queued targets, the most urgent first, while
+    /// there are job slots free for their commands.  Once the run is
+    /// stopped, they fail without running anything.
+    fn dispatch(&mut self) -> io::Result<()> {
+        while !self.queue.is_empty() && self.running.len() < self.jobs {
+            let slot = match self.jobserver {
+                Some(jobserver) if !self.stopped() => match jobserver.try_acquire()? {
+                    Some(slot) => Some(slot),
+                    None => break,
+                },
+                _ => None,
+            };
+            self.queue.sort_by_key(|&(rank, _)| rank);
+            let mut ready: Vec<NodeId> = self.queue.iter().map(|&(_, id)| id).collect();
+            self.priority.order_ready(self.targets, &mut ready);
+            let next = self.queue.iter().position(|&(_, id)| id == ready[0]).unwrap();
+            let (_, id) = self.queue.remove(next);
+            if self.stopped() {
+                self.finish(id, Status::Failed)?;
+            } else {
+                self.update(id, slot)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Whether the run has been interrupted or has seen as many failures
+    /// as `-q` or `--keep-going` allow, so that nothing more is started.
+    fn stopped(&self) -> bool {
+        interrupt::interrupted(self.interrupt)
+            || self.options.failure_limit().is_some_and(|n| self.stats.failed >= n)
+    }
+
+    fn count_parents(&mut self, roots: &[NodeId]) {
+        let mut seen: HashSet<NodeId> = roots.iter().cloned().collect();
+        let mut queue = roots.to_vec();
+        while let Some(id) = queue.pop() {
+            for d in make::dependencies(self.targets, id) {
+                *self.parents.entry(d).or_insert(0) += 1;
+                if seen.insert(d) {
+                    queue.push(d);
+                }
+            }
+        }
+    }
+
+    /// Notes that target `id` is done with its dependencies, removing the
+    /// temporary ones nothing else needs.
+    fn made(&mut self, id: NodeId, status: Status) -> io::Result<()> {
+        for d in make::dependencies(self.targets, id) {
+            let left = match self.parents.get_mut(&d) {
+                Some(n) if *n > 0 => { *n -= 1; *n }
+                _ => continue,
+            };
+            if status == Status::Failed {
+                self.kept.insert(d);
+            }
+            let dep = self.targets.get(d);
+            if left == 0 && dep.has(Flags::Temporary) && self.built.contains(&d) && !self.kept.contains(&d)
+                    && fs::remove_file(dep.bound_name()).is_ok()
+                    && self.options.debug.enabled(debug::MAKEQ) {
+                writeln!(self.out, "...removing temporary {}", dep.bound_name())?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Updates target `id`, running its commands in `slot`.
+    fn update(&mut self, id: NodeId, slot: Option<Slot<'a>>) -> io::Result<()> {
+        let key = self.cache_key(id);
+        if let Some(ref key) = key {
+            if self.restore(id, key)? {
+                self.stats.updated += 1;
+                self.built.insert(id);
+                self.record(id);
+                return self.finish(id, Status::Ok);
+            }
+        }
+        let (targets, env, done) = (self.targets, self.env, &mut self.done);
+        let name = targets.get(id).name();
+        let commands = match profile::span("expand", name, || command::build(targets, env, id, done)) {
+            Ok(c) => c,
+            Err(e) => {
+                writeln!(self.out, "jam: {}", e)?;
+                self.stats.failed += 1;
+                return self.finish(id, Status::Failed);
+            }
+        };
+        self.proceed(Update { id, key, commands: commands.into_iter(), _slot: slot })
+    }
+
+    /// Runs the next command of `update`, going on to the ones after it
+    /// while they need not be waited for: those printed under `-n` or
+    /// written to the script.  Once there are none left, the target is
+    /// up to date.
+    fn proceed(&mut self, mut update: Update<'a>) -> io::Result<()> {
+        for mut cmd in update.commands.by_ref() {
+            if let Some(ref mut script) = self.script {
+                script.command(&cmd)?;
+                continue;
+            }
+            let levels = &self.options.debug;
+            let header = format!("{} {}", cmd.action.name, cmd.target);
+            let mut block = Vec::new();
+            if levels.enabled(debug::MAKE) && (!cmd.quiet() || levels.enabled(debug::MAKEQ)) {
+                writeln!(block, "{}", header)?;
+            }
+            if levels.enabled(debug::EXEC) {
+                writeln!(block, "{}", cmd.text)?;
+            }
+            if self.options.noexec {
+                self.out.write_all(&block)?;
+                continue;
+            }
+
+            cmd.write_response()?;
+            let lane = (1..).find(|&n| self.running.iter().all(|j| j.lane != n)).unwrap();
+            let process = Process::spawn(&cmd.text).inspect_err(|_| {
+                if let Some(ref response) = cmd.response {
+                    let _ = fs::remove_file(&response.path);
+                }
+            })?;
+            self.running.push(Job { update, cmd, header, block, process, start: Instant::now(), lane });
+            return Ok(());
+        }
+
+        let id = update.id;
+        self.stats.updated += 1;
+        if !self.options.noexec && self.script.is_none() {
+            self.built.insert(id);
+        }
+        if let Some(ref key) = update.key {
+            self.store(id, key)?;
+        }
+        self.record(id);
+        self.finish(id, Status::Ok)
+    }
+
+    /// The cache key for target `id`, if it can be cached on this run.
+    fn cache_key(&self, id: NodeId) -> Option<Digest> {
+        if self.cache.is_none() || self.options.noexec || self.script.is_some()
+                || self.targets.get(id).has(Flags::NotFile) {
+            return None;
+        }
+        Some(cache::key(self.targets, self.env, id))
+    }
+
+    /// Restores the outputs of target `id` from the cache, returning
+    /// whether they were there.  A broken cache only costs a rebuild.
+    fn restore(&mut self, id: NodeId, key: &Digest) -> io::Result<bool> {
+        let target = self.targets.get(id);
+        let outputs = cache::outputs(self.targets, id);
+        let restored = self.cache.unwrap().get(key).and_then(|entry| match entry {
+            Some(data) => cache::unpack(&data, &outputs).map(|_| true),
+            None => Ok(false),
+        });
+        match restored {
+            Ok(true) => {
+                for inv in &target.actions {
+                    self.done.insert(&**inv as *const ::action::Invocation);
+                }
+                if self.options.debug.enabled(debug::MAKE) {
+                    writeln!(self.out, "...restored {} from cache...", target.name())?;
+                }
+                Ok(true)
+            }
+            Ok(false) => Ok(false),
+            Err(e) => {
+                writeln!(self.out, "jam: cache: {}", e)?;
+                Ok(false)
+            }
+        }
+    }
+
+    /// Stores the outputs of target `id` in the cache.  Targets whose
+    /// actions did not write every output are left out.
+    fn store(&mut self, id: NodeId, key: &Digest) -> io::Result<()> {
+        let outputs = cache::outputs(self.targets, id);
+        if !outputs.iter().all(|o| ::std::path::Path::new(o).is_file()) {
+            return Ok(());
+        }
+        if let Err(e) = cache::pack(&outputs).and_then(|data| self.cache.unwrap().put(key, &data)) {
+            writeln!(self.out, "jam: cache: {}", e)?;
+        }
+        Ok(())
+    }
+
+    /// Remembers the actions and inputs target `id` is now up to date with.
+    fn record(&mut self, id: NodeId) {
+        if self.options.noexec || self.script.is_some() {
+            return;
+        }
+        if let Some(ref mut state) = self.state {
+            let entry = state.entry(self.targets.get(id).name());
+            entry.command = Some(state::commands(self.targets, self.env, id));
+            entry.inputs = match self.options.content_hash {
+                true => Some(state::inputs(self.targets, id)),
+                false => None,
+            };
+        }
+    }
+
+    /// Waits for one of the running commands to exit and carries on with
+    /// its update, or while targets wait for a jobserver token, for a while
+    /// so that another can be asked for.  Once the run is interrupted, the
+    /// signal is passed on to every command still running.
+    fn wait(&mut self) -> io::Result<()> {
+        let waiting = self.jobserver.is_some() && !self.queue.is_empty() && self.running.len() < self.jobs;
+        loop {
+            let interrupted = interrupt::interrupted(self.interrupt);
+            for i in 0..self.running.len() {
+                if interrupted {
+                    self.running[i].process.interrupt();
+                }
+                if let Some(status) = self.running[i].process.child.try_wait()? {
+                    let job = self.running.remove(i);
+                    return self.complete(job, status);
+                }
+            }
+            thread::sleep(Duration::from_millis(5));
+            if waiting {
+                return Ok(());
+            }
+        }
+    }
+
+    /// Prints the output of `job`, which exited with `status`, and goes on
+    /// to the next command of its update, or fails the target.
+    fn complete(&mut self, job: Job<'a>, status: process::ExitStatus) -> io::Result<()> {
+        let Job { update, cmd, header, mut block, process, start, lane } = job;
+        let time = start.elapsed();
+        profile::record("action", &header, start, lane);
+        if let Some(ref response) = cmd.response {
+            let _ = fs::remove_file(&response.path);
+        }
+        let output = process.output()?;
+        trace!(debug::EXECCMD, "/bin/sh -c for {}: {}", header, status);
+        block.extend_from_slice(&output);
+        self.out.write_all(&block)?;
+        self.out.flush()?;
+        self.log(&cmd, &header, &output)?;
+
+        let id = update.id;
+        if !status.success() && interrupt::interrupted(self.interrupt) {
+            writeln!(self.out, "...interrupted {} {}...", cmd.action.name, cmd.target)?;
+            self.remove_outputs(id, &cmd)?;
+            self.stats.failed += 1;
+            return self.finish(id, Status::Failed);
+        }
+
+        let ok = status.success() || cmd.ignore();
+        self.stats.timings.push(Timing {
+            action: cmd.action.name.clone(),
+            target: cmd.target.clone(),
+            time,
+            ok,
+        });
+        if ok {
+            return self.proceed(update);
+        }
+        writeln!(self.out, "...failed {} {}...", cmd.action.name, cmd.target)?;
+        if !self.options.keep_failed {
+            self.remove_outputs(id, &cmd)?;
+        }
+        self.failures.push(Failure { header, text: cmd.text, output });
+        self.stats.failed += 1;
+        self.finish(id, Status::Failed)
+    }
+
+    /// Removes the targets a failed command may have left half written,
+    /// unless they are `PRECIOUS` or the action keeps state in them
+    /// (`updated`).
+    fn remove_outputs(&mut self, id: NodeId, cmd: &Command) -> io::Result<()> {
+        if cmd.action.has(::action::Flags::Updated) {
+            return Ok(());
+        }
+        let targets = self.targets;
+        let mut removed = HashSet::new();
+        for inv in &targets.get(id).actions {
+            if !Rc::ptr_eq(&inv.action, &cmd.action) {
+                continue;
+            }
+            for t in inv.targets.iter().filter_map(|name| targets.find(name)) {
+                let t = targets.get(t);
+                if !t.has(Flags::Precious) && removed.insert(t.bound_name())
+                        && fs::remove_file(t.bound_name()).is_ok() {
+                    writeln!(self.out, "...removing {}", t.bound_name())?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Adds a command and its output to the log of its target, starting
+    /// the log afresh for the first command of the run.
+    fn log(&mut self, cmd: &Command, header: &str, output: &[u8]) -> io::Result<()> {
+        let dir = match self.options.log_dir {
+            Some(ref dir) => Path::new(dir),
+            None => return Ok(()),
+        };
+        fs::create_dir_all(dir)?;
+        let name: String = cmd.target.chars()
+            .map(|c| if c.is_ascii_alphanumeric() || "+-._".contains(c) { c } else { '_' })
+            .collect();
+        let first = self.logged.insert(cmd.target.clone());
+        let mut file = fs::OpenOptions::new().create(true).write(true)
+            .append(!first).truncate(first)
+            .open(dir.join(name + ".log"))?;
+        writeln!(file, "{}\n{}", header, cmd.text.trim_matches('\n'))?;
+        file.write_all(output)
+    }
+}
+
+/// A command run with `/bin/sh -c`, with what it writes to stdout and
+/// stderr collected in the order it wrote it.
+///
+/// The command runs in a process group of its own, for an interrupt to be
+/// passed on to everything it starts, unless jam's stdin is a terminal.
+/// A command in a background group that reads from the terminal would be
+/// stopped, and there the terminal interrupts the commands itself.
+struct Process {
+    child: process::Child,
+    output: thread::JoinHandle<io::Result<Vec<u8>>>,
+    /// Whether the command leads a process group of its own.
+    group: bool,
+    interrupted: bool,
+}
+
+impl Process {
+    fn spawn(text: &str) -> io::Result<Process> {
+        let (mut reader, writer) = io::pipe()?;
+        let group = !io::stdin().is_terminal();
+        // The command holds the writing ends of the pipe, and is dropped at
+        // the end of the block so that reading stops when the child exits.
+        let child = {
+            let mut command = process::Command::new("/bin/sh");
+            command.arg("-c").arg(text).stdout(writer.try_clone()?).stderr(writer);
+            #[cfg(unix)]
+            {
+                if group {
+                    ::std::os::unix::process::CommandExt::process_group(&mut command, 0);
+                }
+            }
+            command.spawn()?
+        };
+        let output = thread::spawn(move || {
+            let mut output = Vec::new();
+            reader.read_to_end(&mut output).map(|_| output)
+        });
+        Ok(Process { child, output, group, interrupted: false })
+    }
+
+    /// Passes an interrupt on to the command, and everything it started if
+    /// it has a group of its own, the first time it is called.
+    fn interrupt(&mut self) {
+        if !self.interrupted {
+            interrupt::forward(self.child.id(), self.group);
+            self.interrupted = true;
+        }
+    }
+
+    /// What the command wrote, once it has exited.
+    fn output(self) -> io::Result<Vec<u8>> {
+        self.output.join().unwrap_or_else(|_| Ok(Vec::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::{Action, Flags};
+    use jam_depgraph::Edge;
+    use std::rc::Rc;
+    use std::str;
+    use testing::TempDir;
+    use variable::Assign;
+
+    /// Updates `target` from `sources`, which it depends on, with `action`.
+    fn build(ts: &mut Targets, action: Action, target: &str, sources: Vec<&str>) {
+        let (target, sources) = (List::from(vec![target]), List::from(sources));
+        ts.invoke(&Rc::new(action), &target, &sources);
+        ts.add_edges(&target, &sources, Edge::Depends);
+    }
+
+    /// Links no-such-prog from no-such.o, compiled from main.c in `dir`.
+    fn targets(dir: &TempDir, env: &Environment, roots: &List) -> Targets {
+        dir.write("main.c", "int main() { return 0; }\n");
+        let mut ts = Targets::new();
+        build(&mut ts, Action::new("Link", vec![], "cc -o $(<) $(>)"),
+              "no-such-prog", vec!["no-such.o"]);
+        build(&mut ts, Action::new("Cc", vec![Flags::Quietly], "cc -c $(>)"),
+              "no-such.o", vec!["main.c"]);
+        let search = List::from(vec![dir.path().to_str().unwrap()]);
+        ts.set_on(&List::from(vec!["main.c"]), "SEARCH", Assign::Set, &search);
+        make::bind(&mut ts, env, roots);
+        ts
+    }
+
+    #[test]
+    fn dry_run_prints_commands_in_dependency_order() {
+        let dir = TempDir::new("dry-run-test");
+        let env = Environment::new();
+        let roots = List::from(vec!["no-such-prog"]);
+        let ts = targets(&dir, &env, &roots);
+
+        let options = Options::parse(vec!["-n"]).unwrap();
+        let mut out = Vec::new();
+        let stats = Executor::new(&ts, &env, &options, &mut out).run(&roots).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), format!(
+                   "Cc no-such.o\ncc -c {}\nLink no-such-prog\ncc -o no-such-prog no-such.o\n\
+                    ...updated 2 target(s)...\n", dir.name("main.c")));
+        assert_eq!((stats.updated, stats.failed, stats.skipped), (2, 0, 0));
+        assert!(stats.timings.is_empty());
+    }
+
+    #[test]
+    fn quiet_actions_hidden_by_default_display() {
+        let dir = TempDir::new("quiet-test");
+        let env = Environment::new();
+        let roots = List::from(vec!["no-such-prog"]);
+        let ts = targets(&dir, &env, &roots);
+
+        let mut options = Options::parse(vec!["-n"]).unwrap();
+        options.debug = debug::Levels::default();
+        let mut out = Vec::new();
+        Executor::new(&ts, &env, &options, &mut out).run(&roots).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "Link no-such-prog\n...updated 2 target(s)...\n");
+    }
+
+    #[test]
+    fn output_captured_and_logged() {
+        let dir = TempDir::new("log-test");
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        build(&mut ts, Action::new("Check", vec![], "echo out; echo err >&2; exit 1"),
+               "<dir>no/such", vec![]);
+        let roots = List::from(vec!["<dir>no/such"]);
+        make::bind(&mut ts, &env, &roots);
+
+        let options = Options { log_dir: Some(dir.name("logs")), ..Options::default() };
+        let mut out = Vec::new();
+        let stats = Executor::new(&ts, &env, &options, &mut out).run(&roots).unwrap();
+        let log = fs::read_to_string(dir.join("logs/_dir_no_such.log")).unwrap();
+        assert_eq!(stats.failed, 1);
+        assert_eq!(str::from_utf8(&out).unwrap(), "\
+Check <dir>no/such
+out
+err
+...failed Check <dir>no/such...
+...output of 1 failed action(s)...
+Check <dir>no/such
+echo out; echo err >&2; exit 1
+out
+err
+...failed updating 1 target(s)...
+");
+        assert_eq!(stats.timings.len(), 1);
+        assert!(!stats.timings[0].ok);
+        assert_eq!(log, "Check <dir>no/such\necho out; echo err >&2; exit 1\nout\nerr\n");
+    }
+
+    #[test]
+    fn interrupted_commands_stop_the_run() {
+        let dir = TempDir::new("interrupt-test");
+        let (partial, precious, next) = (dir.name("partial"), dir.name("precious"), dir.name("next"));
+
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let slow = Rc::new(Action::new("Build", vec![], "touch $(<) ; sleep 10"));
+        ts.invoke(&slow, &List::from(vec![partial.as_str(), precious.as_str()]), &List::new());
+        let id = ts.find(&precious).unwrap();
+        ts.get_mut(id).flags.push(::target::Flags::Precious);
+        build(&mut ts, Action::new("Build", vec![], "touch $(<)"), &next, vec![]);
+        let roots = List::from(vec![partial.as_str(), next.as_str()]);
+        make::bind(&mut ts, &env, &roots);
+
+        let flag = AtomicBool::new(false);
+        let options = Options::default();
+        let mut out = Vec::new();
+        let start = Instant::now();
+        let stats = thread::scope(|s| {
+            s.spawn(|| {
+                thread::sleep(Duration::from_millis(200));
+                flag.store(true, ::std::sync::atomic::Ordering::SeqCst);
+            });
+            Executor::new(&ts, &env, &options, &mut out).interrupt(&flag).run(&roots).unwrap()
+        });
+        let left: Vec<bool> = [&partial, &precious, &next].iter().map(|p| Path::new(p).exists()).collect();
+
+        assert!(start.elapsed() < Duration::from_secs(5));
+        assert!(stats.interrupted);
+        assert_eq!(left, vec![false, true, false]);
+        assert_eq!(str::from_utf8(&out).unwrap(), format!("\
+Build {0}
+...interrupted Build {0}...
+...removing {0}
+...failed updating 1 target(s)...
+", partial));
+    }
+
+    #[test]
+    fn jobs_run_at_once_after_their_dependencies() {
+        let dir = TempDir::new("jobs-test");
+        let (all, parts) = (dir.name("all"), vec![dir.name("a"), dir.name("b"), dir.name("c")]);
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        // Each part waits for the other two to start before it finishes, so
+        // they only get made if all three run at once.
+        let barrier = format!("touch $(<).started ; n=0 ; \
+                               while [ ! -e {0}.started ] || [ ! -e {1}.started ] || [ ! -e {2}.started ] ; do \
+                               n=`expr $n + 1` ; [ $n -lt 1000 ] || exit 1 ; sleep 0.01 ; done ; echo $(<) > $(<)",
+                              parts[0], parts[1], parts[2]);
+        for part in &parts {
+            build(&mut ts, Action::new("Build", vec![], &barrier), part, vec![]);
+        }
+        build(&mut ts, Action::new("Build", vec![], "cat $(>) > $(<)"), &all,
+              parts.iter().map(|p| p.as_str()).collect());
+        let roots = List::from(vec![all.as_str()]);
+        make::bind(&mut ts, &env, &roots);
+
+        let options = Options { jobs: Some(3), debug: debug::Levels::none(), ..Options::default() };
+        let stats = Executor::new(&ts, &env, &options, &mut Vec::new()).run(&roots).unwrap();
+        assert_eq!((stats.updated, stats.failed), (4, 0));
+        assert_eq!(fs::read_to_string(&all).unwrap(), parts.iter().map(|p| p.clone() + "\n").collect::<String>());
+    }
+
+    #[test]
+    fn jobs_take_jobserver_tokens() {
+        let dir = TempDir::new("jobserver-jobs-test");
+        let fifo = dir.join("fifo");
+        assert!(process::Command::new("mkfifo").arg(&fifo).status().unwrap().success());
+        let mut make = fs::OpenOptions::new().read(true).write(true).open(&fifo).unwrap();
+        make.write_all(b"+").unwrap();
+        let jobserver = Jobserver::open(&::jobserver::Auth::Fifo(fifo)).unwrap();
+
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        let log = dir.name("log");
+        // Each job notes when it starts and ends in the log, waiting for a
+        // second job to have started before it ends.
+        let job = format!("echo + >> {0} ; n=0 ; while [ `grep -c + {0}` -lt 2 ] ; do \
+                           n=`expr $n + 1` ; [ $n -lt 1000 ] || exit 1 ; sleep 0.01 ; done ; \
+                           echo - >> {0} ; touch $(<)", log);
+        let parts = vec![dir.name("a"), dir.name("b"), dir.name("c")];
+        for part in &parts {
+            build(&mut ts, Action::new("Build", vec![], &job), part, vec![]);
+        }
+        let roots = List::from(parts.iter().map(|p| p.as_str()).collect::<Vec<_>>());
+        make::bind(&mut ts, &env, &roots);
+
+        // The implicit slot and the one token run two of the three at once.
+        let options = Options { debug: debug::Levels::none(), ..Options::default() };
+        let stats = Executor::new(&ts, &env, &options, &mut Vec::new()).jobserver(&jobserver).run(&roots).unwrap();
+        assert_eq!((stats.updated, stats.failed), (3, 0));
+        let running = fs::read_to_string(&log).unwrap().lines()
+            .scan(0, |n, l| { *n = if l == "+" { *n + 1 } else { *n - 1 }; Some(*n) })
+            .max();
+        assert_eq!(running, Some(2));
+        drop(jobserver);
+        let mut returned = [0u8];
+        make.read_exact(&mut returned).unwrap();
+        assert_eq!(&returned, b"+");
+    }
+
+    #[test]
+    fn failed_targets_and_used_temporaries_removed() {
+        let dir = TempDir::new("remove-test");
+        let (lib, gen, bad) = (dir.name("lib"), dir.name("gen"), dir.name("bad"));
+        let run = |keep_failed: bool| {
+            let env = Environment::new();
+            let mut ts = Targets::new();
+            build(&mut ts, Action::new("Build", vec![], "cat $(>) > $(<)"), &lib, vec![&gen]);
+            build(&mut ts, Action::new("Build", vec![], "echo gen > $(<)"), &gen, vec![]);
+            build(&mut ts, Action::new("Build", vec![], "echo bad > $(<) ; exit 1"), &bad, vec![]);
+            let id = ts.find(&gen).unwrap();
+            ts.get_mut(id).flags.push(::target::Flags::Temporary);
+            let roots = List::from(vec![lib.as_str(), bad.as_str()]);
+            make::bind(&mut ts, &env, &roots);
+
+            let options = Options { keep_failed, ..Options::default() };
+            let mut out = Vec::new();
+            Executor::new(&ts, &env, &options, &mut out).run(&roots).unwrap();
+            let left: Vec<bool> = [&lib, &gen, &bad].iter().map(|p| Path::new(p).exists()).collect();
+            (left, String::from_utf8(out).unwrap())
+        };
+
+        let (left, out) = run(false);
+        let (kept, _) = run(true);
+        assert_eq!(left, vec![true, false, false]);
+        assert!(out.contains(&format!("...failed Build {0}...\n...removing {0}\n", bad)));
+        assert_eq!(kept, vec![true, false, true]);
+    }
+
+    #[test]
+    fn failures_skip_dependents_and_can_stop_the_run() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        build(&mut ts, Action::new("Link", vec![], "true"), "no-such-prog", vec!["no-such-a", "no-such-b"]);
+        build(&mut ts, Action::new("Check", vec![], "exit 1"), "no-such-a", vec![]);
+        build(&mut ts, Action::new("Check", vec![], "exit 1"), "no-such-b", vec![]);
+        build(&mut ts, Action::new("Check", vec![], "true"), "no-such-c", vec![]);
+        let roots = List::from(vec!["no-such-prog", "no-such-c"]);
+        make::bind(&mut ts, &env, &roots);
+        let run = |args: Vec<&str>| {
+            let mut options = Options::parse(args).unwrap();
+            options.debug = debug::Levels::none();
+            let stats = Executor::new(&ts, &env, &options, &mut Vec::new()).run(&roots).unwrap();
+            (stats.updated, stats.failed, stats.skipped)
+        };
+
+        assert_eq!(run(vec![]), (1, 2, 1));
+        assert_eq!(run(vec!["--keep-going=2"]), (0, 2, 1));
+        assert_eq!(run(vec!["-q"]), (0, 1, 1));
+    }
+
+    #[test]
+    fn missing_sources_fail_their_dependents() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        build(&mut ts, Action::new("Link", vec![], "true"), "no-such-prog", vec!["no-such.o", "no-such-lib.a"]);
+        build(&mut ts, Action::new("Cc", vec![], "true"), "no-such.o", vec!["no-such.c"]);
+        ts.add_edges(&List::from(vec!["all"]), &List::from(vec!["no-such-prog"]), Edge::Depends);
+        let all = ts.find("all").unwrap();
+        ts.get_mut(all).flags.push(::target::Flags::NotFile);
+        let roots = List::from(vec!["all"]);
+        make::bind(&mut ts, &env, &roots);
+
+        let mut out = Vec::new();
+        let stats = Executor::new(&ts, &env, &Options::default(), &mut out).run(&roots).unwrap();
+        assert_eq!((stats.updated, stats.failed, stats.skipped), (0, 0, 2));
+        assert_eq!((stats.cantfind, stats.cantmake), (2, 2));
+        assert_eq!(str::from_utf8(&out).unwrap(), "\
+don't know how to make no-such.c
+...skipped no-such.o for lack of no-such.c...
+don't know how to make no-such-lib.a
+...skipped no-such-prog for lack of no-such.o...
+...can't find 2 target(s)...
+...can't make 2 target(s)...
+...skipped 2 target(s)...
+");
+    }
+
+    #[test]
+    fn response_files_removed_after_running() {
+        let dir = TempDir::new("response-test");
+        let copy = dir.join("copy");
+        let mut env = Environment::new();
+        env.set("JAMRESPONSE", Assign::Set, &List::from(vec![""]));
+        let mut ts = Targets::new();
+        let action = format!("cp $(>) {}", copy.display());
+        let sources = vec!["a.c", "b.c", "c.c", "d.c", "e.c", "f.c"];
+        for name in &sources {
+            dir.write(name, "");
+        }
+        env.set("SEARCH", Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        build(&mut ts, Action::new("Link", vec![Flags::Maxline(60)], &action), "no-such-prog", sources.clone());
+        let roots = List::from(vec!["no-such-prog"]);
+        make::bind(&mut ts, &env, &roots);
+
+        let options = Options { debug: debug::Levels::none(), ..Options::default() };
+        let mut out = Vec::new();
+        let stats = Executor::new(&ts, &env, &options, &mut out).run(&roots).unwrap();
+        let contents = fs::read_to_string(&copy).unwrap();
+        assert_eq!(stats.updated, 1);
+        assert_eq!(contents, sources.iter().map(|s| dir.name(s) + "\n").collect::<String>());
+        let leftovers = fs::read_dir(::std::env::temp_dir()).unwrap()
+            .filter(|e| e.as_ref().unwrap().file_name().to_string_lossy()
+                    .starts_with(&format!("jam{}-", process::id())))
+            .count();
+        assert_eq!(leftovers, 0);
+    }
+}
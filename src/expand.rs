@@ -0,0 +1,269 @@
+use debug;
+use env::Environment;
+use glob;
+use list::List;
+use path::Path;
+
+/// Expands the `$(...)` variable references in a single word, producing
+/// the product of the expanded pieces as in jam's `var_expand`.
+///
+/// Subscripts (`$(X[2])`, `$(X[2-3])`, `$(X[2-])`) and the `:E=`, `:J=`,
+/// `:M=`, `:U` and `:L` modifiers are understood, as are the file name
+/// modifiers `:G`, `:R`, `:D`, `:B`, `:S`, `:M` and `:P`.  `:M=pattern`
+/// keeps the elements that match a glob pattern, as `switch` cases are
+/// matched.
+pub fn expand(word: &str, env: &Environment) -> List {
+    let start = match word.find("$(") {
+        Some(i) => i,
+        None => return List::from(vec![word]),
+    };
+    let (prefix, rest) = (&word[..start], &word[start + 2..]);
+
+    let mut depth = 1;
+    let mut end = None;
+    for (i, c) in rest.char_indices() {
+        match c {
+            '(' => depth += 1,
+            ')' => {
+                depth -= 1;
+                if depth == 0 {
+                    end = Some(i);
+                    break;
+                }
+            }
+            _ => {}
+        }
+    }
+    let end = match end {
+        Some(i) => i,
+        None => return List::from(vec![word]),
+    };
+    let (inner, suffix) = (&rest[..end], &rest[end + 1..]);
+
+    let mut values = List::new();
+    for name in expand(inner, env).iter() {
+        values.extend(&lookup(name, env));
+    }
+    if values.is_empty() {
+        return values;
+    }
+
+    let suffixes = expand(suffix, env);
+    let mut result = List::new();
+    for v in values.iter() {
+        for s in suffixes.iter() {
+            result.append(format!("{}{}{}", prefix, v, s));
+        }
+    }
+    trace!(debug::COMPILE, "expand {} = {}", word, result);
+    result
+}
+
+/// Expands the variable references in the text of an action, copying
+/// everything else (including whitespace and newlines) verbatim.  Each
+/// word with a reference is replaced by its expansion joined with spaces.
+pub fn expand_string(text: &str, env: &Environment) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut word = String::new();
+    for c in text.chars() {
+        if c.is_whitespace() {
+            flush_word(&mut out, &mut word, env);
+            out.push(c);
+        } else {
+            word.push(c);
+        }
+    }
+    flush_word(&mut out, &mut word, env);
+    out
+}
+
+fn flush_word(out: &mut String, word: &mut String, env: &Environment) {
+    if word.contains("$(") {
+        let values: Vec<String> = expand(word, env).iter().cloned().collect();
+        out.push_str(&values.join(" "));
+    } else {
+        out.push_str(word);
+    }
+    word.clear();
+}
+
+fn lookup(reference: &str, env: &Environment) -> List {
+    let split = reference.find(['[', ':']).unwrap_or(reference.len());
+    let (name, mut rest) = reference.split_at(split);
+    let mut value = env.get(name).map(|v| v.value().clone()).unwrap_or_default();
+
+    if rest.starts_with('[') {
+        let close = rest.find(']').unwrap_or(rest.len());
+        value = subscript(&value, &rest[1..close]);
+        rest = if close < rest.len() { &rest[close + 1..] } else { "" };
+    }
+
+    let mut edits = Edits::default();
+    for modifier in rest.split(':').filter(|m| !m.is_empty()) {
+        let (flags, arg) = match modifier.find('=') {
+            Some(i) => (&modifier[..i], Some(&modifier[i + 1..])),
+            None => (modifier, None),
+        };
+        // In `:BS=.c` only the last flag takes the value.
+        let last = flags.chars().count().saturating_sub(1);
+        for (i, flag) in flags.chars().enumerate() {
+            edits.add(flag, if i == last { arg } else { None });
+        }
+    }
+    edits.apply(value)
+}
+
+/// The parts of a file name that `:G`, `:R`, `:D`, `:B`, `:S` and `:M`
+/// refer to, in the order `Edits::parts` keeps them.
+const PARTS: &str = "GRDBSM";
+
+/// The modifiers of a variable reference, which jam applies in a fixed
+/// order whatever order they are written in.
+#[derive(Default)]
+struct Edits<'a> {
+    /// For each of `PARTS`, what to replace it with, or `None` to keep it.
+    /// Naming a part without a value keeps only the parts named.
+    parts: [Option<&'a str>; 6],
+    selected: bool,
+    file: bool,
+    parent: bool,
+    upshift: bool,
+    downshift: bool,
+    empty: Option<&'a str>,
+    join: Option<&'a str>,
+    matching: Option<&'a str>,
+}
+
+impl<'a> Edits<'a> {
+    fn add(&mut self, flag: char, arg: Option<&'a str>) {
+        match (flag, arg) {
+            ('M', Some(pattern)) => self.matching = Some(pattern),
+            ('P', _) => self.parent = true,
+            ('U', _) => self.upshift = true,
+            ('L', _) => self.downshift = true,
+            ('E', v) => self.empty = Some(v.unwrap_or("")),
+            ('J', v) => self.join = Some(v.unwrap_or("")),
+            (flag, v) => if let Some(i) = PARTS.find(flag) {
+                if v.is_none() && !self.selected {
+                    self.selected = true;
+                    self.parts = [Some(""); 6];
+                }
+                self.file = true;
+                self.parts[i] = v;
+            },
+        }
+    }
+
+    fn apply(&self, value: List) -> List {
+        let value = match self.empty {
+            Some(e) if value.is_empty() => List::from(vec![e]),
+            _ => value,
+        };
+        let mut words: Vec<String> = value.iter()
+            .filter(|s| self.matching.is_none_or(|p| glob::matches(p, s)))
+            .map(|s| self.edit(s))
+            .collect();
+        match self.join {
+            Some(sep) if !words.is_empty() => words = vec![words.join(sep)],
+            _ => {}
+        }
+        List::from(words)
+    }
+
+    fn edit(&self, word: &str) -> String {
+        let mut word = word.to_string();
+        if self.file || self.parent {
+            let mut path = Path::parse(&word);
+            for (flag, part) in PARTS.chars().zip(self.parts.iter()) {
+                if let Some(part) = *part {
+                    *path.part_mut(flag).unwrap() = part.to_string();
+                }
+            }
+            if self.parent {
+                path.parent();
+            }
+            word = path.to_string();
+        }
+        if self.upshift {
+            word = word.to_uppercase();
+        }
+        if self.downshift {
+            word = word.to_lowercase();
+        }
+        word
+    }
+}
+
+fn subscript(value: &List, range: &str) -> List {
+    let mut parts = range.splitn(2, '-');
+    let first = parts.next().and_then(|s| s.parse::<usize>().ok()).unwrap_or(1).max(1);
+    let last = match parts.next() {
+        None => first,
+        Some("") => value.length(),
+        Some(s) => s.parse::<usize>().unwrap_or(first),
+    };
+    let last = last.min(value.length());
+    if first > last {
+        return List::new();
+    }
+    value.sublist(first - 1, last - first + 1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use variable::Assign;
+
+    fn env() -> Environment<'static> {
+        let mut e = Environment::new();
+        e.set("X", Assign::Set, &List::from(vec!["a", "b", "c"]));
+        e.set("Y", Assign::Set, &List::from(vec!["1", "2"]));
+        e.set("N", Assign::Set, &List::from(vec!["X"]));
+        e
+    }
+
+    #[test]
+    fn words_without_references() {
+        assert_eq!(expand("plain", &env()), List::from(vec!["plain"]));
+    }
+
+    #[test]
+    fn products() {
+        let e = env();
+        assert_eq!(expand("-I$(Y)", &e), List::from(vec!["-I1", "-I2"]));
+        assert_eq!(expand("$(X)$(Y)", &e), List::from(vec!["a1", "a2", "b1", "b2", "c1", "c2"]));
+        assert_eq!(expand("x$(UNSET)y", &e), List::new());
+        assert_eq!(expand("$($(N))", &e), List::from(vec!["a", "b", "c"]));
+    }
+
+    #[test]
+    fn subscripts_and_modifiers() {
+        let e = env();
+        assert_eq!(expand("$(X[2])", &e), List::from(vec!["b"]));
+        assert_eq!(expand("$(X[2-])", &e), List::from(vec!["b", "c"]));
+        assert_eq!(expand("$(X[1-2]:U)", &e), List::from(vec!["A", "B"]));
+        assert_eq!(expand("$(X:J=,)", &e), List::from(vec!["a,b,c"]));
+        assert_eq!(expand("$(UNSET:E=none)", &e), List::from(vec!["none"]));
+        assert_eq!(expand("$(X:M=[ac])", &e), List::from(vec!["a", "c"]));
+        assert_eq!(expand("$(X:M=*z*)", &e), List::new());
+    }
+
+    #[test]
+    fn file_modifiers() {
+        let mut e = env();
+        e.set("F", Assign::Set, &List::from(vec!["<g>src/main.c", "lib.a(x.o)"]));
+        assert_eq!(expand("$(F:S=.o)", &e), List::from(vec!["<g>src/main.o", "lib.o(x.o)"]));
+        assert_eq!(expand("$(F:BS)", &e), List::from(vec!["main.c", "lib.a"]));
+        assert_eq!(expand("$(F[1]:G=:D=obj)", &e), List::from(vec!["obj/main.c"]));
+        assert_eq!(expand("$(F[1]:G)", &e), List::from(vec!["<g>"]));
+        assert_eq!(expand("$(F[1]:R=build:P)", &e), List::from(vec!["<g>build/src"]));
+        assert_eq!(expand("$(F[2]:M)", &e), List::from(vec!["(x.o)"]));
+        assert_eq!(expand("$(UNSET:E=x.c:S=.o)", &e), List::from(vec!["x.o"]));
+    }
+
+    #[test]
+    fn action_text() {
+        let e = env();
+        assert_eq!(expand_string("\n\tcc $(Y) -o $(UNSET) x\n", &e), "\n\tcc 1 2 -o  x\n");
+    }
+}
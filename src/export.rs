@@ -0,0 +1,154 @@
+use std::io::{self, Write};
+use graph::Targets;
+use jam_depgraph::{Edge, NodeId};
+use json;
+use list::List;
+use target::Target;
+
+/// The formats the dependency graph can be written in with `--graph`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Format {
+    Dot,
+    Json,
+}
+
+impl Format {
+    pub fn parse(s: &str) -> Option<Format> {
+        match s {
+            "dot" => Some(Format::Dot),
+            "json" => Some(Format::Json),
+            _ => None,
+        }
+    }
+}
+
+/// Writes the targets reachable from `roots`, or every target if `roots`
+/// is empty, with their `DEPENDS` and `INCLUDES` edges.
+pub fn write(targets: &Targets, roots: &List, format: Format, out: &mut dyn Write) -> io::Result<()> {
+    let ids = if roots.is_empty() {
+        targets.graph().ids().collect()
+    } else {
+        let roots: Vec<NodeId> = roots.iter().filter_map(|n| targets.find(n)).collect();
+        targets.graph().reachable(&roots)
+    };
+    match format {
+        Format::Dot => dot(targets, &ids, out),
+        Format::Json => json(targets, &ids, out),
+    }
+}
+
+fn flags(t: &Target) -> Vec<String> {
+    t.flags.iter().map(|f| f.to_string()).collect()
+}
+/// Quotes `s` as a DOT string, where only `"` and `\` need escaping
+/// and `\n` starts a new line of a label.
+/// and `\\n` starts a new line of a label.
+fn quote(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' | '\\' => { out.push('\\'); out.push(c); }
+            '\n' => out.push_str("\\n"),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+fn dot(targets: &Targets, ids: &[NodeId], out: &mut dyn Write) -> io::Result<()> {
+    writeln!(out, "digraph jam {{")?;
+    for &id in ids {
+        let t = targets.get(id);
+        let mut label = format!("{}\n{}", t.name(), t.fate);
+        if t.bound_name() != t.name() {
+            label = format!("{}\n{}", label, t.bound_name());
+        }
+        let flags = flags(t);
+        if !flags.is_empty() {
+            label = format!("{}\n{}", label, flags.join(" "));
+        }
+        let shape = if t.actions.is_empty() { "ellipse" } else { "box" };
+        writeln!(out, "  n{} [label={}, shape={}];", id.index(), quote(&label), shape)?;
+    }
+    for &id in ids {
+        for &d in targets.graph().edges(id, Edge::Depends) {
+            writeln!(out, "  n{} -> n{};", id.index(), d.index())?;
+        }
+        for &i in targets.graph().edges(id, Edge::Includes) {
+            writeln!(out, "  n{} -> n{} [style=dashed];", id.index(), i.index())?;
+        }
+    }
+    writeln!(out, "}}")
+}
+
+fn json(targets: &Targets, ids: &[NodeId], out: &mut dyn Write) -> io::Result<()> {
+    let names = |kind, id| targets.graph().edges(id, kind).iter().map(|&d| targets.get(d).name());
+    writeln!(out, "{{\"targets\": [")?;
+    for (n, &id) in ids.iter().enumerate() {
+        let t = targets.get(id);
+        write!(out, "  {{\"name\": {}, \"bound\": {}, \"fate\": {}, \"flags\": {}, \
+                     \"actions\": {}, \"depends\": {}, \"includes\": {}}}",
+               json::string(t.name()),
+               json::string(t.bound_name()),
+               json::string(&t.fate.to_string()),
+               json::array(flags(t)),
+               json::array(t.actions.iter().map(|a| a.action.name.as_str())),
+               json::array(names(Edge::Depends, id)),
+               json::array(names(Edge::Includes, id)))?;
+        writeln!(out, "{}", if n + 1 < ids.len() { "," } else { "" })?;
+    }
+    writeln!(out, "]}}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+    use target::Flags;
+
+    fn targets() -> Targets {
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["all"]), &List::from(vec!["prog"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["main.c"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["main.c"]), &List::from(vec!["main.h"]), Edge::Includes);
+        ts.add_edges(&List::from(vec!["other"]), &List::from(vec!["main.c"]), Edge::Depends);
+        let all = ts.find("all").unwrap();
+        ts.get_mut(all).flags.push(Flags::NotFile);
+        ts
+    }
+
+    #[test]
+    fn dot_output() {
+        let mut out = Vec::new();
+        write(&targets(), &List::from(vec!["prog"]), Format::Dot, &mut out).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "\
+digraph jam {
+  n1 [label=\"prog\\ninit\", shape=ellipse];
+  n2 [label=\"main.c\\ninit\", shape=ellipse];
+  n3 [label=\"main.h\\ninit\", shape=ellipse];
+  n1 -> n2;
+  n2 -> n3 [style=dashed];
+}
+");
+    }
+
+    #[test]
+    fn dot_quoting() {
+        assert_eq!(quote("a \"b\"\nc\\d\tτ"), "\"a \\\"b\\\"\\nc\\\\d\tτ\"");
+    }
+
+    #[test]
+    fn json_output() {
+        let mut out = Vec::new();
+        write(&targets(), &List::new(), Format::Json, &mut out).unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert!(out.starts_with("{\"targets\": [\n  {\"name\": \"all\", \"bound\": \"all\", \
+                                 \"fate\": \"init\", \"flags\": [\"NOTFILE\"], \"actions\": [], \
+                                 \"depends\": [\"prog\"], \"includes\": []},\n"));
+        assert!(out.contains("{\"name\": \"main.c\", \"bound\": \"main.c\", \"fate\": \"init\", \
+                              \"flags\": [], \"actions\": [], \"depends\": [], \"includes\": [\"main.h\"]}"));
+        assert_eq!(out.matches("\"name\"").count(), 5);
+        assert!(out.ends_with("}\n]}\n"));
+    }
+}
@@ -0,0 +1,171 @@
+//! Jam-style glob matching, as used by `switch` case labels, the `GLOB`
+//! builtin and `:M` variable modifiers.
+//!
+//! Supported wildcards:
+//!
+//! * `?` matches any single character
+//! * `*` matches zero or more characters
+//! * `[chars]` matches any single character in `chars`; ranges such as
+//!   `a-z` are allowed
+//! * `[^chars]` matches any single character not in `chars`
+//! * `\c` matches the character `c` literally
+
+/// Returns true if `s` matches the glob `pattern` in its entirety.
+pub fn matches(pattern: &str, s: &str) -> bool {
+    let p: Vec<char> = pattern.chars().collect();
+    let s: Vec<char> = s.chars().collect();
+    match_from(&p, &s)
+}
+
+fn match_from(p: &[char], s: &[char]) -> bool {
+    let (mut pi, mut si) = (0, 0);
+    // Position of the last `*` seen in the pattern and the string index it
+    // is currently assumed to have consumed up to, for backtracking.
+    let mut star: Option<(usize, usize)> = None;
+
+    while si < s.len() {
+        if pi < p.len() {
+            match p[pi] {
+                '*' => {
+                    star = Some((pi, si));
+                    pi += 1;
+                    continue;
+                }
+                '?' => {
+                    pi += 1;
+                    si += 1;
+                    continue;
+                }
+                '[' => {
+                    if let Some((matched, len)) = match_class(&p[pi..], s[si]) {
+                        if matched {
+                            pi += len;
+                            si += 1;
+                            continue;
+                        }
+                    } else if s[si] == '[' {
+                        // An unterminated class is matched literally.
+                        pi += 1;
+                        si += 1;
+                        continue;
+                    }
+                }
+                '\\' if pi + 1 < p.len() => {
+                    if p[pi + 1] == s[si] {
+                        pi += 2;
+                        si += 1;
+                        continue;
+                    }
+                }
+                c => {
+                    if c == s[si] {
+                        pi += 1;
+                        si += 1;
+                        continue;
+                    }
+                }
+            }
+        }
+
+        match star {
+            Some((sp, ss)) => {
+                pi = sp + 1;
+                si = ss + 1;
+                star = Some((sp, ss + 1));
+            }
+            None => return false,
+        }
+    }
+
+    p[pi..].iter().all(|&c| c == '*')
+}
+
+/// Matches a single character against the `[...]` class at the start of
+/// `p`.  Returns whether it matched and the length of the class in the
+/// pattern, or `None` if the class is not terminated.
+fn match_class(p: &[char], c: char) -> Option<(bool, usize)> {
+    let mut i = 1;
+    let negate = p.get(i) == Some(&'^');
+    if negate {
+        i += 1;
+    }
+
+    let mut matched = false;
+    let mut first = true;
+    loop {
+        let lo = match p.get(i) {
+            None => return None,
+            Some(&']') if !first => break,
+            Some(&lo) => lo,
+        };
+        first = false;
+
+        if p.get(i + 1) == Some(&'-') && p.get(i + 2).is_some_and(|&hi| hi != ']') {
+            let hi = p[i + 2];
+            if lo <= c && c <= hi {
+                matched = true;
+            }
+            i += 3;
+        } else {
+            if lo == c {
+                matched = true;
+            }
+            i += 1;
+        }
+    }
+
+    Some((matched != negate, i + 1))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::matches;
+
+    #[test]
+    fn literal_patterns() {
+        assert!(matches("", ""));
+        assert!(matches("foo.c", "foo.c"));
+        assert!(!matches("foo.c", "foo.h"));
+        assert!(!matches("foo", "foobar"));
+        assert!(!matches("foobar", "foo"));
+    }
+
+    #[test]
+    fn question_mark() {
+        assert!(matches("foo.?", "foo.c"));
+        assert!(!matches("foo.?", "foo.cc"));
+        assert!(!matches("?", ""));
+    }
+
+    #[test]
+    fn star() {
+        assert!(matches("*", ""));
+        assert!(matches("*", "anything"));
+        assert!(matches("*.c", "main.c"));
+        assert!(!matches("*.c", "main.cc"));
+        assert!(matches("a*b*c", "aXbYc"));
+        assert!(matches("a*b*c", "abbbc"));
+        assert!(!matches("a*b*c", "acb"));
+        assert!(matches("**.h", "x.h"));
+    }
+
+    #[test]
+    fn character_classes() {
+        assert!(matches("[abc]", "b"));
+        assert!(!matches("[abc]", "d"));
+        assert!(matches("[a-z]*", "jam"));
+        assert!(!matches("[a-z]*", "Jam"));
+        assert!(matches("[^a-z]*", "Jam"));
+        assert!(!matches("[^a-z]*", "jam"));
+        assert!(matches("*.[ch]", "lexer.h"));
+        assert!(matches("[]]", "]"));
+        assert!(matches("[a-]", "-"));
+    }
+
+    #[test]
+    fn escapes_and_unterminated_classes() {
+        assert!(matches("\\*", "*"));
+        assert!(!matches("\\*", "x"));
+        assert!(matches("[abc", "[abc"));
+    }
+}
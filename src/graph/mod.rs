@@ -1,9 +1,159 @@
+use std::collections::HashMap;
+use std::rc::Rc;
+use action::{Action, Invocation};
+use env::Environment;
+use jam_depgraph::{DepGraph, Edge, NodeId};
+use list::List;
+use target::Target;
+use variable::Assign;
 
+/// All targets known to jam, keyed by name, along with the `DEPENDS` and
+/// `INCLUDES` relationships between them.
+#[derive(Debug)]
+pub struct Targets {
+    graph: DepGraph<Target>,
+    by_name: HashMap<String, NodeId>
+}
 
+impl Targets {
+    pub fn new() -> Targets {
+        Targets {
+            graph: DepGraph::new(),
+            by_name: HashMap::new()
+        }
+    }
+
+    pub fn find(&self, name: &str) -> Option<NodeId> {
+        self.by_name.get(name).cloned()
+    }
+
+    pub fn bind(&mut self, name: &str) -> NodeId {
+        if let Some(id) = self.find(name) {
+            return id;
+        }
+        let id = self.graph.add_node(Target::new(name));
+        self.by_name.insert(name.to_string(), id);
+        id
+    }
+
+    pub fn add_edges(&mut self, from: &List, to: &List, kind: Edge) {
+        for f in from.iter() {
+            let f = self.bind(f);
+            for t in to.iter() {
+                let t = self.bind(t);
+                self.graph.add_edge(f, t, kind);
+            }
+        }
+    }
+
+    /// Evaluates `name on targets op value ;`.
+    pub fn set_on(&mut self, targets: &List, name: &str, op: Assign, value: &List) {
+        for t in targets.iter() {
+            let id = self.bind(t);
+            self.graph.node_mut(id).set_var(name, op, value);
+        }
+    }
+
+    /// Attaches one invocation of `action` to each of `targets`, updating
+    /// them from `sources`, as invoking a rule that has actions does.
+    pub fn invoke(&mut self, action: &Rc<Action>, targets: &List, sources: &List) {
+        let inv = Rc::new(Invocation { action: action.clone(), targets: targets.clone(), sources: sources.clone() });
+        for t in targets.iter() {
+            let id = self.bind(t);
+            self.get_mut(id).actions.push(inv.clone());
+        }
+    }
+
+    /// Runs `f` with the named target's variables pushed onto `env`, as
+    /// for `on target { }` and `[ on target Rule args ]`.  Assignments made
+    /// meanwhile to those variables stay with the target; any others last.
+    /// An unknown target contributes no variables.
+    pub fn with_scope<F, R>(&mut self, name: &str, env: &mut Environment, f: F) -> R
+        where F: FnOnce(&mut Targets, &mut Environment) -> R
+    {
+        let id = match self.find(name) {
+            Some(id) => id,
+            None => return f(self, env),
+        };
+        let saved = env.push(self.get(id).settings());
+        let result = f(self, env);
+        *self.get_mut(id).settings_mut() = env.pop(saved);
+        result
+    }
+
+    /// Evaluates `[ on target return $(name) ]`: the target-specific value
+    /// of `name` if there is one, otherwise the value from `env`.
+    pub fn var_on(&self, target: &str, env: &Environment, name: &str) -> List {
+        let value = match self.find(target).and_then(|id| self.get(id).var(name)) {
+            Some(v) => Some(v),
+            None => env.get(name),
+        };
+        value.map(|v| v.value().clone()).unwrap_or_default()
+    }
+
+    pub fn get(&self, id: NodeId) -> &Target { self.graph.node(id) }
+
+    pub fn get_mut(&mut self, id: NodeId) -> &mut Target { self.graph.node_mut(id) }
+
+    pub fn graph(&self) -> &DepGraph<Target> { &self.graph }
+}
+
+impl Default for Targets {
+    fn default() -> Targets { Targets::new() }
+}
 
 #[cfg(test)]
 mod tests {
+    use super::*;
+
     #[test]
     fn it_works() {
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn on_target_assignment() {
+        let mut ts = Targets::new();
+        ts.set_on(&List::from(vec!["a.o", "b.o"]), "CCFLAGS", Assign::Set, &List::from(vec!["-g"]));
+        let a = ts.find("a.o").unwrap();
+        let b = ts.find("b.o").unwrap();
+        assert_ne!(a, b);
+        assert_eq!(*ts.get(a).var("CCFLAGS").unwrap().value(), List::from(vec!["-g"]));
+        assert!(ts.find("c.o").is_none());
+    }
+
+    #[test]
+    fn on_target_return() {
+        let mut env = Environment::new();
+        env.set("LOCATE", Assign::Set, &List::from(vec!["build"]));
+        let mut ts = Targets::new();
+        ts.set_on(&List::from(vec!["a.o"]), "LOCATE", Assign::Set, &List::from(vec!["obj"]));
+
+        assert_eq!(ts.var_on("a.o", &env, "LOCATE"), List::from(vec!["obj"]));
+        assert_eq!(ts.var_on("b.o", &env, "LOCATE"), List::from(vec!["build"]));
+        assert_eq!(ts.var_on("a.o", &env, "SEARCH"), List::new());
+        assert!(ts.find("b.o").is_none());
+    }
+
+    #[test]
+    fn on_target_rule_sees_target_scope() {
+        let mut env = Environment::new();
+        env.set("CCFLAGS", Assign::Set, &List::from(vec!["-O2"]));
+        let mut ts = Targets::new();
+        ts.set_on(&List::from(vec!["a.o"]), "CCFLAGS", Assign::Set, &List::from(vec!["-g"]));
+        let flags = ts.with_scope("a.o", &mut env, |_, scope| {
+            scope.set("CCFLAGS", Assign::Append, &List::from(vec!["-O"]));
+            scope.set("LIBS", Assign::Set, &List::from(vec!["-lm"]));
+            scope["CCFLAGS"].value().clone()
+        });
+        assert_eq!(flags, List::from(vec!["-g", "-O"]));
+        // The target keeps its own variables; other assignments persist.
+        let a = ts.find("a.o").unwrap();
+        assert_eq!(*ts.get(a).var("CCFLAGS").unwrap().value(), List::from(vec!["-g", "-O"]));
+        assert_eq!(*env["CCFLAGS"].value(), List::from(vec!["-O2"]));
+        assert_eq!(*env["LIBS"].value(), List::from(vec!["-lm"]));
+
+        ts.with_scope("b.o", &mut env, |_, scope| scope.set("X", Assign::Set, &List::from(vec!["x"])));
+        assert_eq!(*env["X"].value(), List::from(vec!["x"]));
+        assert!(ts.find("b.o").is_none());
+    }
+}
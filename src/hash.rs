@@ -0,0 +1,212 @@
+use std::fmt;
+use std::fs::File;
+use std::io::{self, Read};
+use std::path::Path;
+use std::str::FromStr;
+
+const K: [u32; 64] = [
+    0x428a2f98, 0x71374491, 0xb5c0fbcf, 0xe9b5dba5, 0x3956c25b, 0x59f111f1, 0x923f82a4, 0xab1c5ed5,
+    0xd807aa98, 0x12835b01, 0x243185be, 0x550c7dc3, 0x72be5d74, 0x80deb1fe, 0x9bdc06a7, 0xc19bf174,
+    0xe49b69c1, 0xefbe4786, 0x0fc19dc6, 0x240ca1cc, 0x2de92c6f, 0x4a7484aa, 0x5cb0a9dc, 0x76f988da,
+    0x983e5152, 0xa831c66d, 0xb00327c8, 0xbf597fc7, 0xc6e00bf3, 0xd5a79147, 0x06ca6351, 0x14292967,
+    0x27b70a85, 0x2e1b2138, 0x4d2c6dfc, 0x53380d13, 0x650a7354, 0x766a0abb, 0x81c2c92e, 0x92722c85,
+    0xa2bfe8a1, 0xa81a664b, 0xc24b8b70, 0xc76c51a3, 0xd192e819, 0xd6990624, 0xf40e3585, 0x106aa070,
+    0x19a4c116, 0x1e376c08, 0x2748774c, 0x34b0bcb5, 0x391c0cb3, 0x4ed8aa4a, 0x5b9cca4f, 0x682e6ff3,
+    0x748f82ee, 0x78a5636f, 0x84c87814, 0x8cc70208, 0x90befffa, 0xa4506ceb, 0xbef9a3f7, 0xc67178f2,
+];
+
+const INITIAL: [u32; 8] = [
+    0x6a09e667, 0xbb67ae85, 0x3c6ef372, 0xa54ff53a, 0x510e527f, 0x9b05688c, 0x1f83d9ab, 0x5be0cd19,
+];
+
+/// A SHA-256 digest.  Cache keys are shared between machines and users,
+/// so they must be hard to collide on purpose.
+#[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
+pub struct Digest([u8; 32]);
+
+impl fmt::Display for Digest {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for b in &self.0 {
+            write!(f, "{:02x}", b)?;
+        }
+        Ok(())
+    }
+}
+
+/// A string that is not 64 hex digits.
+#[derive(Debug, PartialEq)]
+pub struct ParseDigestError;
+
+impl FromStr for Digest {
+    type Err = ParseDigestError;
+
+    fn from_str(s: &str) -> Result<Digest, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(ParseDigestError);
+        }
+        let mut d = [0; 32];
+        for (i, b) in d.iter_mut().enumerate() {
+            *b = u8::from_str_radix(&s[i * 2..i * 2 + 2], 16).map_err(|_| ParseDigestError)?;
+        }
+        Ok(Digest(d))
+    }
+}
+
+#[derive(Clone)]
+pub struct Hasher {
+    state: [u32; 8],
+    /// Input not yet making up a whole 64-byte block.
+    pending: Vec<u8>,
+    length: u64,
+}
+
+impl Default for Hasher {
+    fn default() -> Hasher {
+        Hasher { state: INITIAL, pending: Vec::with_capacity(64), length: 0 }
+    }
+}
+
+impl Hasher {
+    pub fn new() -> Hasher { Hasher::default() }
+
+    pub fn update(&mut self, mut bytes: &[u8]) {
+        self.length += bytes.len() as u64;
+        if !self.pending.is_empty() {
+            let n = (64 - self.pending.len()).min(bytes.len());
+            self.pending.extend_from_slice(&bytes[..n]);
+            bytes = &bytes[n..];
+            if self.pending.len() < 64 {
+                return;
+            }
+            let block = ::std::mem::take(&mut self.pending);
+            self.compress(&block);
+        }
+        let mut blocks = bytes.chunks_exact(64);
+        for block in &mut blocks {
+            self.compress(block);
+        }
+        self.pending.extend_from_slice(blocks.remainder());
+    }
+
+    /// Adds a string, terminated so that `"ab", "c"` and `"a", "bc"`
+    /// hash differently.
+    pub fn update_str(&mut self, s: &str) {
+        self.update(s.as_bytes());
+        self.update(&[0]);
+    }
+
+    pub fn update_file<P: AsRef<Path>>(&mut self, path: P) -> io::Result<()> {
+        let mut f = File::open(path)?;
+        let mut buf = [0; 8192];
+        loop {
+            match f.read(&mut buf)? {
+                0 => return Ok(()),
+                n => self.update(&buf[..n]),
+            }
+        }
+    }
+
+    pub fn finish(&self) -> Digest {
+        let mut h = self.clone();
+        let bits = h.length.wrapping_mul(8);
+        let mut padding = vec![0x80];
+        padding.resize(1 + (119 - h.pending.len()) % 64, 0);
+        padding.extend_from_slice(&bits.to_be_bytes());
+        h.update(&padding);
+        let mut d = [0; 32];
+        for (out, word) in d.chunks_exact_mut(4).zip(h.state.iter()) {
+            out.copy_from_slice(&word.to_be_bytes());
+        }
+        Digest(d)
+    }
+
+    fn compress(&mut self, block: &[u8]) {
+        let mut w = [0u32; 64];
+        for (i, word) in block.chunks_exact(4).enumerate() {
+            w[i] = u32::from_be_bytes([word[0], word[1], word[2], word[3]]);
+        }
+        for i in 16..64 {
+            let s0 = w[i - 15].rotate_right(7) ^ w[i - 15].rotate_right(18) ^ (w[i - 15] >> 3);
+            let s1 = w[i - 2].rotate_right(17) ^ w[i - 2].rotate_right(19) ^ (w[i - 2] >> 10);
+            w[i] = w[i - 16].wrapping_add(s0).wrapping_add(w[i - 7]).wrapping_add(s1);
+        }
+        let [mut a, mut b, mut c, mut d, mut e, mut f, mut g, mut h] = self.state;
+        for i in 0..64 {
+            let s1 = e.rotate_right(6) ^ e.rotate_right(11) ^ e.rotate_right(25);
+            let ch = (e & f) ^ (!e & g);
+            let t1 = h.wrapping_add(s1).wrapping_add(ch).wrapping_add(K[i]).wrapping_add(w[i]);
+            let s0 = a.rotate_right(2) ^ a.rotate_right(13) ^ a.rotate_right(22);
+            let maj = (a & b) ^ (a & c) ^ (b & c);
+            let t2 = s0.wrapping_add(maj);
+            h = g;
+            g = f;
+            f = e;
+            e = d.wrapping_add(t1);
+            d = c;
+            c = b;
+            b = a;
+            a = t1.wrapping_add(t2);
+        }
+        for (s, v) in self.state.iter_mut().zip(&[a, b, c, d, e, f, g, h]) {
+            *s = s.wrapping_add(*v);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+
+    #[test]
+    fn known_values() {
+        assert_eq!(Hasher::new().finish().to_string(),
+                   "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855");
+        let mut h = Hasher::new();
+        h.update(b"a");
+        assert_eq!(h.finish().to_string(),
+                   "ca978112ca1bbdcafac231b39a23dc4da786eff8147c4e72b9807785afee48bb");
+        // Two blocks, fed across the boundary between them.
+        let mut h = Hasher::new();
+        h.update(b"abcdbcdecdefdefgefghfghighijhijk");
+        h.update(b"ijkljklmklmnlmnomnopnopq");
+        assert_eq!(h.finish().to_string(),
+                   "248d6a61d20638b8e5c026930c3e6039a33ce45964ff2167f6ecedd419db06c1");
+        let mut h = Hasher::new();
+        for _ in 0..1000 {
+            h.update(&[b'a'; 1000]);
+        }
+        assert_eq!(h.finish().to_string(),
+                   "cdc76e5c9914fb9281a1c7e284d73e67f1809a48a497200e046d39ccc7112cd0");
+    }
+
+    #[test]
+    fn strings_are_delimited() {
+        let mut a = Hasher::new();
+        a.update_str("ab");
+        a.update_str("c");
+        let mut b = Hasher::new();
+        b.update_str("a");
+        b.update_str("bc");
+        assert_ne!(a.finish(), b.finish());
+    }
+
+    #[test]
+    fn digests_round_trip() {
+        let d = Hasher::new().finish();
+        assert_eq!(d.to_string().parse::<Digest>().unwrap(), d);
+        assert!("xyz".parse::<Digest>().is_err());
+    }
+
+    #[test]
+    fn hashing_files() {
+        let dir = TempDir::new("hash-test");
+        let path = dir.write("big", &"0123456789abcdef".repeat(1000));
+        let mut a = Hasher::new();
+        a.update_file(&path).unwrap();
+        let mut b = Hasher::new();
+        b.update(&::std::fs::read(&path).unwrap());
+        assert_eq!(a.finish(), b.finish());
+        assert!(Hasher::new().update_file("no-such-file").is_err());
+    }
+}
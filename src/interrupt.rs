@@ -0,0 +1,53 @@
+//! Interruption of a run by `SIGINT`, `SIGTERM` or `SIGHUP`.
+//!
+//! The handler only notes that a signal arrived.  The executor, which
+//! polls for the note while commands run, passes the signal on to each
+//! command and, if it runs in a process group of its own, everything it
+//! started, then stops the run.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+/// The flag the signal handler sets.
+pub fn flag() -> &'static AtomicBool {
+    &INTERRUPTED
+}
+
+#[cfg(unix)]
+extern "C" fn handler(_: ::libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Catches the signals that interrupt a run.  System calls they cut short
+/// are restarted, so that only the executor's polling notices them.
+#[cfg(unix)]
+pub fn install() {
+    for &sig in &[::libc::SIGHUP, ::libc::SIGINT, ::libc::SIGTERM] {
+        unsafe {
+            let mut action: ::libc::sigaction = ::std::mem::zeroed();
+            action.sa_sigaction = handler as extern "C" fn(::libc::c_int) as ::libc::sighandler_t;
+            action.sa_flags = ::libc::SA_RESTART;
+            ::libc::sigemptyset(&mut action.sa_mask);
+            ::libc::sigaction(sig, &action, ::std::ptr::null_mut());
+        }
+    }
+}
+
+/// Sends `SIGINT` to process `pid`, or with `group` to the process group
+/// it leads.
+#[cfg(unix)]
+pub fn forward(pid: u32, group: bool) {
+    let pid = pid as i32;
+    unsafe { ::libc::kill(if group { -pid } else { pid }, ::libc::SIGINT); }
+}
+
+#[cfg(not(unix))]
+pub fn install() {}
+
+#[cfg(not(unix))]
+pub fn forward(_: u32, _: bool) {}
+
+pub fn interrupted(flag: &AtomicBool) -> bool {
+    flag.load(Ordering::SeqCst)
+}
@@ -0,0 +1,292 @@
+//! A client of the GNU make jobserver, so that jam run from a Makefile or
+//! a Cargo build script shares the parent's job slots instead of adding
+//! its own.
+//!
+//! The parent passes `--jobserver-auth=R,W` (or the older
+//! `--jobserver-fds=R,W`) in `MAKEFLAGS`, naming a pipe inherited from it,
+//! or `--jobserver-auth=fifo:PATH`, naming a fifo.  Each byte in it is a
+//! token for one job beyond the one every child may always run.  A token
+//! is taken by reading a byte and given back by writing the same byte.
+
+use std::cell::{Cell, RefCell};
+use std::env;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Read, Write};
+use std::path::PathBuf;
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
+use std::thread;
+
+/// Where the jobserver's tokens are, as named in `MAKEFLAGS`.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Auth {
+    /// A pipe inherited from the parent, by its read and write ends.
+    Fds(i32, i32),
+    Fifo(PathBuf),
+}
+
+impl Auth {
+    /// Finds the jobserver in make's flags, the last one given winning.
+    pub fn parse(makeflags: &str) -> Option<Auth> {
+        let value = makeflags.split_whitespace()
+            .filter_map(|f| f.strip_prefix("--jobserver-auth=").or_else(|| f.strip_prefix("--jobserver-fds=")))
+            .next_back()?;
+        if let Some(path) = value.strip_prefix("fifo:") {
+            return Some(Auth::Fifo(PathBuf::from(path)));
+        }
+        let mut fds = value.splitn(2, ',').map(|fd| fd.parse::<i32>());
+        match (fds.next(), fds.next()) {
+            (Some(Ok(r)), Some(Ok(w))) if r >= 0 && w >= 0 => Some(Auth::Fds(r, w)),
+            _ => None,
+        }
+    }
+}
+
+/// Opens a copy of descriptor `fd`, inherited from the parent, leaving
+/// the original for the commands jam runs to inherit in turn.
+#[cfg(unix)]
+fn inherited(fd: i32) -> io::Result<File> {
+    use std::os::unix::io::FromRawFd;
+    unsafe {
+        if ::libc::fcntl(fd, ::libc::F_GETFD) == -1 {
+            return Err(io::Error::new(io::ErrorKind::NotFound, format!("descriptor {} is not open", fd)));
+        }
+        match ::libc::dup(fd) {
+            -1 => Err(io::Error::last_os_error()),
+            copy => Ok(File::from_raw_fd(copy)),
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn inherited(fd: i32) -> io::Result<File> {
+    Err(io::Error::new(io::ErrorKind::Unsupported, format!("cannot inherit descriptor {}", fd)))
+}
+
+/// A connection to the jobserver, handing out job slots.
+#[derive(Debug)]
+pub struct Jobserver {
+    read: File,
+    write: File,
+    /// Whether the slot jam always has is free.
+    implicit: Cell<bool>,
+    /// The thread waiting for tokens for `try_acquire`, once started.
+    helper: RefCell<Option<Helper>>,
+}
+
+/// A thread reading a token from the jobserver each time it is asked to,
+/// so that jam can go on reaping its commands while it waits for one.
+#[derive(Debug)]
+struct Helper {
+    requests: Sender<()>,
+    tokens: Receiver<io::Result<u8>>,
+    /// Whether a token was asked for and has not been received yet.
+    asked: bool,
+}
+
+impl Helper {
+    fn start(read: File, write: File) -> Helper {
+        let (requests, asked) = mpsc::channel();
+        let (sent, tokens) = mpsc::channel();
+        thread::spawn(move || {
+            // Stops when the jobserver, and with it the sender, is dropped.
+            for () in asked {
+                match token(&read) {
+                    Ok(byte) => {
+                        // A token read after the jobserver went away goes
+                        // back to the pipe at once.
+                        if sent.send(Ok(byte)).is_err() {
+                            let _ = (&write).write_all(&[byte]);
+                        }
+                    }
+                    Err(e) => {
+                        let _ = sent.send(Err(e));
+                    }
+                }
+            }
+        });
+        Helper { requests, tokens, asked: false }
+    }
+}
+
+/// Reads one token from the jobserver, waiting for one to be there.
+fn token(mut read: &File) -> io::Result<u8> {
+    let mut byte = [0u8];
+    loop {
+        match read.read(&mut byte) {
+            Ok(1) => return Ok(byte[0]),
+            Ok(_) => return Err(io::Error::new(io::ErrorKind::UnexpectedEof, "jobserver closed")),
+            Err(ref e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+impl Jobserver {
+    /// Connects to the jobserver named in `CARGO_MAKEFLAGS`, `MAKEFLAGS` or
+    /// `MFLAGS`, if any is.
+    pub fn from_env() -> io::Result<Option<Jobserver>> {
+        let flags = ["CARGO_MAKEFLAGS", "MAKEFLAGS", "MFLAGS"].iter().filter_map(|v| env::var(v).ok()).next();
+        match flags.as_deref().and_then(Auth::parse) {
+            Some(auth) => Jobserver::open(&auth).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    pub fn open(auth: &Auth) -> io::Result<Jobserver> {
+        let (read, write) = match *auth {
+            Auth::Fds(r, w) => (inherited(r)?, inherited(w)?),
+            Auth::Fifo(ref path) => {
+                // Opening a fifo for reading and writing does not wait for
+                // another process to open it.
+                let fifo = OpenOptions::new().read(true).write(true).open(path)?;
+                (fifo.try_clone()?, fifo)
+            }
+        };
+        Ok(Jobserver { read, write, implicit: Cell::new(true), helper: RefCell::new(None) })
+    }
+
+    /// Takes a job slot: the implicit one if it is free, otherwise a token,
+    /// waiting for one if there are none to be had.
+    pub fn acquire(&self) -> io::Result<Slot<'_>> {
+        if self.implicit.replace(false) {
+            return Ok(Slot { jobserver: self, token: None });
+        }
+        token(&self.read).map(|byte| Slot { jobserver: self, token: Some(byte) })
+    }
+
+    /// Takes a job slot if one is to be had now: the implicit one if it is
+    /// free, otherwise a token.  Without one, a token is asked for, to be
+    /// taken by a later call once it comes.
+    pub fn try_acquire(&self) -> io::Result<Option<Slot<'_>>> {
+        if self.implicit.replace(false) {
+            return Ok(Some(Slot { jobserver: self, token: None }));
+        }
+        let mut helper = self.helper.borrow_mut();
+        if helper.is_none() {
+            *helper = Some(Helper::start(self.read.try_clone()?, self.write.try_clone()?));
+        }
+        let helper = helper.as_mut().unwrap();
+        if !helper.asked {
+            helper.requests.send(()).map_err(|_| io::Error::other("jobserver helper exited"))?;
+            helper.asked = true;
+        }
+        match helper.tokens.try_recv() {
+            Ok(token) => {
+                helper.asked = false;
+                token.map(|byte| Some(Slot { jobserver: self, token: Some(byte) }))
+            }
+            Err(TryRecvError::Empty) => Ok(None),
+            Err(TryRecvError::Disconnected) => Err(io::Error::other("jobserver helper exited")),
+        }
+    }
+}
+
+impl Drop for Jobserver {
+    /// Gives back a token the helper read but nobody took.
+    fn drop(&mut self) {
+        if let Some(helper) = self.helper.get_mut() {
+            if let Ok(Ok(byte)) = helper.tokens.try_recv() {
+                let _ = (&self.write).write_all(&[byte]);
+            }
+        }
+    }
+}
+
+/// A job slot, given back when dropped.
+#[derive(Debug)]
+pub struct Slot<'a> {
+    jobserver: &'a Jobserver,
+    /// The byte read for the token, or nothing for the implicit slot.
+    token: Option<u8>,
+}
+
+impl<'a> Drop for Slot<'a> {
+    fn drop(&mut self) {
+        match self.token {
+            Some(byte) => {
+                let _ = (&self.jobserver.write).write_all(&[byte]);
+            }
+            None => self.jobserver.implicit.set(true),
+        }
+    }
+}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+    use std::os::unix::io::AsRawFd;
+    use std::process;
+
+    #[test]
+    fn parses_makeflags() {
+        assert_eq!(Auth::parse(" -j --jobserver-auth=3,4"), Some(Auth::Fds(3, 4)));
+        assert_eq!(Auth::parse("-j8 --jobserver-fds=5,6 --jobserver-auth=fifo:/tmp/GMfifo1"),
+                   Some(Auth::Fifo(PathBuf::from("/tmp/GMfifo1"))));
+        assert_eq!(Auth::parse("kr --jobserver-fds=7,8 -j"), Some(Auth::Fds(7, 8)));
+        assert_eq!(Auth::parse("--jobserver-auth=-2,-2"), None);
+        assert_eq!(Auth::parse("--jobserver-auth=3"), None);
+        assert_eq!(Auth::parse("-j4 -k"), None);
+    }
+
+    #[test]
+    fn tokens_from_a_fifo() {
+        let dir = TempDir::new("jobserver-test");
+        let path = dir.join("fifo");
+        assert!(process::Command::new("mkfifo").arg(&path).status().unwrap().success());
+        let mut make = OpenOptions::new().read(true).write(true).open(&path).unwrap();
+        make.write_all(b"+-").unwrap();
+
+        let jobserver = Jobserver::open(&Auth::Fifo(path.clone())).unwrap();
+        let first = jobserver.acquire().unwrap();
+        let second = jobserver.acquire().unwrap();
+        let third = jobserver.acquire().unwrap();
+        assert_eq!((first.token, second.token, third.token), (None, Some(b'+'), Some(b'-')));
+        drop(first);
+        assert_eq!(jobserver.acquire().unwrap().token, None);
+        drop(second);
+        drop(third);
+
+        // Both tokens went back, for make to take again.
+        let mut returned = [0u8; 2];
+        make.read_exact(&mut returned).unwrap();
+        assert_eq!(&returned, b"+-");
+    }
+
+    #[test]
+    fn tokens_from_inherited_pipe() {
+        let (reader, mut writer) = io::pipe().unwrap();
+        writer.write_all(b"|").unwrap();
+        let auth = Auth::parse(&format!("-j --jobserver-auth={},{}", reader.as_raw_fd(), writer.as_raw_fd()));
+        let jobserver = Jobserver::open(&auth.unwrap()).unwrap();
+        let _implicit = jobserver.acquire().unwrap();
+        assert_eq!(jobserver.acquire().unwrap().token, Some(b'|'));
+        // The copies stay open, so the token is back in the pipe.
+        drop(writer);
+        assert_eq!(jobserver.acquire().unwrap().token, Some(b'|'));
+        assert!(Jobserver::open(&Auth::Fds(9999, 9999)).is_err());
+    }
+
+    #[test]
+    fn tokens_taken_without_waiting() {
+        let (mut reader, mut writer) = io::pipe().unwrap();
+        let jobserver = Jobserver::open(&Auth::Fds(reader.as_raw_fd(), writer.as_raw_fd())).unwrap();
+        let implicit = jobserver.try_acquire().unwrap().unwrap();
+        assert_eq!(implicit.token, None);
+        assert!(jobserver.try_acquire().unwrap().is_none());
+
+        writer.write_all(b"+").unwrap();
+        let token = loop {
+            match jobserver.try_acquire().unwrap() {
+                Some(slot) => break slot,
+                None => thread::sleep(::std::time::Duration::from_millis(1)),
+            }
+        };
+        assert_eq!(token.token, Some(b'+'));
+        drop(token);
+        drop(implicit);
+        drop(jobserver);
+        let mut returned = [0u8];
+        reader.read_exact(&mut returned).unwrap();
+        assert_eq!(&returned, b"+");
+    }
+}
@@ -0,0 +1,69 @@
+use std::fmt::Write;
+
+/// Quotes `s` as a JSON string.
+pub fn string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => { let _ = write!(out, "\\u{:04x}", c as u32); }
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
+/// Reads the JSON string `s` starts with, returning it unquoted along with
+/// the rest of `s`.
+pub fn parse_string(s: &str) -> Option<(String, &str)> {
+    let mut chars = s.strip_prefix('"')?.char_indices();
+    let mut out = String::new();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '"' => return Some((out, &s[i + 2..])),
+            '\\' => out.push(match chars.next()?.1 {
+                'n' => '\n',
+                'r' => '\r',
+                't' => '\t',
+                'u' => {
+                    let hex: String = (0..4).filter_map(|_| chars.next().map(|(_, c)| c)).collect();
+                    char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?
+                }
+                c => c,
+            }),
+            c => out.push(c),
+        }
+    }
+    None
+}
+
+/// Formats the strings as a JSON array.
+pub fn array<I, S>(items: I) -> String
+    where I: IntoIterator<Item = S>, S: AsRef<str>
+{
+    let items: Vec<String> = items.into_iter().map(|s| string(s.as_ref())).collect();
+    format!("[{}]", items.join(", "))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escaping() {
+        assert_eq!(string("plain"), "\"plain\"");
+        assert_eq!(string("a \"b\"\\\n\u{1}"), "\"a \\\"b\\\"\\\\\\n\\u0001\"");
+        assert_eq!(array(vec!["a", "b"]), "[\"a\", \"b\"]");
+        assert_eq!(array(Vec::<String>::new()), "[]");
+        let quoted = string("a \"b\"\\\n\u{1}");
+        assert_eq!(parse_string(&format!("{}, 1", quoted)), Some(("a \"b\"\\\n\u{1}".to_string(), ", 1")));
+        assert_eq!(parse_string("\"open"), None);
+        assert_eq!(parse_string("bare"), None);
+    }
+}
@@ -1,7 +1,10 @@
+use std::rc::Rc;
 use action;
 use env;
+use glob;
 use list;
 use lexer::Token;
+use variable::Assign;
 
 pub struct ParseError {}
 pub type ParseResult<T> = Result<T, ParseError>;
@@ -17,6 +20,7 @@ pub struct GenericBinaryOp<Op, L, R, O> where L: Parse<Out=O>, R: Parse<Out=O> {
     right: Box<R>,
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum BooleanOp {
     And,
     Or,
@@ -34,6 +38,7 @@ impl<L,R> Parse for BooleanExpr<L, R> where L: Parse<Out=bool>, R: Parse<Out=boo
     }
 }
 
+#[derive(Clone, Copy, Debug, PartialEq)]
 pub enum CompareOp {
     Equals,
     NotEquals,
@@ -70,9 +75,10 @@ macro_rules! declaration {
     (
         $name:ident => { $($k:ident: $v:ty,)* }
     ) => {
+        #[derive(Debug)]
         pub struct $name {
-            name: String,
-            $( $k: $v,)*
+            pub name: String,
+            $( pub $k: $v,)*
         }
     }
 }
@@ -85,4 +91,92 @@ declaration!{ ActionDeclaration => {
 
 declaration!{ RuleDeclaration => {
     args: Vec<String>,
-}}
\ No newline at end of file
+    body: Rc<Vec<Stmt>>,
+}}
+
+#[derive(Debug)]
+pub struct Case<B> {
+    pub pattern: String,
+    pub block: B,
+}
+
+/// A `switch value { case pattern : block ... }` statement.
+#[derive(Debug)]
+pub struct SwitchStmt<B> {
+    pub value: Vec<Arg>,
+    pub cases: Vec<Case<B>>,
+}
+
+impl<B> SwitchStmt<B> {
+    /// The block to execute for `value`, the expanded value of the switch:
+    /// that of the first case whose pattern matches its first element.
+    pub fn select(&self, value: &list::List) -> Option<&B> {
+        let value = value.get(0).unwrap_or("");
+        self.cases.iter()
+            .find(|c| glob::matches(&c.pattern, value))
+            .map(|c| &c.block)
+    }
+}
+
+/// A word of a Jamfile, which expands to a list, or a `[ ]` rule call,
+/// which stands for what the rule returns.
+#[derive(Debug)]
+pub enum Arg {
+    Word(String),
+    Call(Box<Func>),
+}
+
+/// The calls that can appear in `[ ]`.
+#[derive(Debug)]
+pub enum Func {
+    /// `[ Rule args : ... ]`
+    Invoke(Arg, Vec<Vec<Arg>>),
+    /// `[ on target Rule args : ... ]`
+    On(Arg, Arg, Vec<Vec<Arg>>),
+    /// `[ on target return list ]`
+    OnReturn(Arg, Vec<Arg>),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum BinaryOp {
+    Boolean(BooleanOp),
+    Compare(CompareOp),
+}
+
+/// The condition of an `if` or `while`.
+#[derive(Debug)]
+pub enum Expr {
+    /// True if the argument expands to any non-empty word.
+    Value(Arg),
+    /// True if every word of the argument is in the list.
+    In(Arg, Vec<Arg>),
+    Not(Box<Expr>),
+    Binary(BinaryOp, Box<Expr>, Box<Expr>),
+}
+
+/// A statement of a Jamfile.
+#[derive(Debug)]
+pub enum Stmt {
+    Block(Vec<Stmt>),
+    Include(Vec<Arg>),
+    /// `Rule args : ... ;`
+    Invoke(Arg, Vec<Vec<Arg>>),
+    /// `names op value ;`
+    Assign(Arg, Assign, Vec<Arg>),
+    /// `names on targets op value ;`
+    AssignOn(Arg, Vec<Arg>, Assign, Vec<Arg>),
+    /// `local names ;` or `local names = value ;`, lasting to the end of
+    /// the enclosing block.
+    Local(Vec<Arg>, Option<Vec<Arg>>),
+    Break,
+    Continue,
+    Return(Vec<Arg>),
+    For(String, Vec<Arg>, Vec<Stmt>),
+    Switch(SwitchStmt<Vec<Stmt>>),
+    If(Expr, Vec<Stmt>, Option<Box<Stmt>>),
+    While(Expr, Vec<Stmt>),
+    Rule(RuleDeclaration),
+    /// `on target statement`
+    On(Arg, Box<Stmt>),
+    Actions(ActionDeclaration),
+}
@@ -0,0 +1,723 @@
+use std::cmp::Ordering;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::Path;
+use std::rc::Rc;
+use action::Action;
+use debug;
+use env::{Environment, Saved};
+use expand;
+use glob;
+use graph::Targets;
+use jam_depgraph::{Edge, NodeId};
+use list::List;
+use make;
+use profile;
+use regex::Regex;
+use target::Flags;
+use variable::{Assign, Variable};
+use super::ast::{Arg, BinaryOp, BooleanOp, CompareOp, Expr, Func, Stmt};
+use super::parse;
+use super::profile::RuleProfile;
+use super::rule::Rule;
+
+#[derive(Debug)]
+pub enum EvalError {
+    /// An included file could not be read.
+    Io(String, io::Error),
+    Parse(String),
+    /// `EXIT` was called with this message.
+    Exit(String),
+}
+
+impl fmt::Display for EvalError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            EvalError::Io(ref file, ref e) => write!(f, "{}: {}", file, e),
+            EvalError::Parse(ref msg) => f.write_str(msg),
+            EvalError::Exit(ref msg) => f.write_str(msg),
+        }
+    }
+}
+
+/// How a statement finished, so that `break`, `continue` and `return` can
+/// leave the loops and rules around it.
+enum Flow {
+    Normal,
+    Break,
+    Continue,
+    Return(List),
+}
+
+/// Runs the statements of Jamfiles, as jam's `compile_*` functions do.
+/// The rules and actions declared are kept here; variables go to the
+/// `Environment` and targets, dependencies and target-specific variables
+/// to the `Targets` each call is given.
+#[derive(Default)]
+pub struct Interpreter {
+    rules: HashMap<String, Rule>,
+    actions: HashMap<String, Rc<Action>>,
+    /// The calls made to each rule, counted under `-d+10` or `--profile`.
+    profile: Option<RuleProfile>,
+    /// The `HDRSCAN` patterns compiled so far.
+    patterns: HashMap<String, Regex>,
+}
+
+impl Interpreter {
+    pub fn new() -> Interpreter {
+        let profiling = debug::enabled(debug::PROFILE) || profile::enabled();
+        Interpreter { profile: if profiling { Some(RuleProfile::new()) } else { None }, ..Interpreter::default() }
+    }
+
+    /// Prints how long the rules called so far took, under `-d+10`.
+    pub fn trace(&self) {
+        if let Some(ref profile) = self.profile {
+            profile.trace();
+        }
+    }
+
+    /// Parses and runs `data`, the contents of Jamfile `file`.
+    pub fn include(&mut self, targets: &mut Targets, env: &mut Environment, file: &str,
+                   data: &[u8]) -> Result<(), EvalError> {
+        let stmts = profile::span("parse", file, || parse(file, data)).map_err(EvalError::Parse)?;
+        profile::span("eval", file, || self.block(targets, env, &stmts))?;
+        Ok(())
+    }
+
+    /// Scans the files of the targets reachable from `roots` for the
+    /// headers they include, as jam's `make0` does.  For each target with
+    /// `HDRSCAN` and `HDRRULE` set, the first group of each match of the
+    /// `HDRSCAN` patterns in a line of its file is passed, with the target,
+    /// to the `HDRRULE` rule, which runs with the target's variables.  The
+    /// headers it declares are scanned in turn.
+    pub fn scan_headers(&mut self, targets: &mut Targets, env: &mut Environment,
+                        roots: &List) -> Result<(), EvalError> {
+        let mut seen = HashSet::new();
+        let mut queue: Vec<NodeId> = roots.iter().filter_map(|n| targets.find(n)).collect();
+        while let Some(id) = queue.pop() {
+            if !seen.insert(id) {
+                continue;
+            }
+            let name = targets.get(id).name().to_string();
+            let scan = targets.var_on(&name, env, "HDRSCAN");
+            let rule = targets.var_on(&name, env, "HDRRULE");
+            if let (false, Some(rule)) = (scan.is_empty(), rule.get(0)) {
+                let rule = rule.to_string();
+                profile::span("scan", &name, || self.scan(targets, env, id, &scan, &rule))?;
+            }
+            for &kind in &[Edge::Depends, Edge::Includes] {
+                queue.extend(targets.graph().edges(id, kind).iter().rev().cloned());
+            }
+        }
+        Ok(())
+    }
+
+    /// Scans the file of target `id` with `patterns`, calling `rule` with
+    /// the headers found.  A file that is not there has none.
+    fn scan(&mut self, targets: &mut Targets, env: &mut Environment, id: NodeId,
+            patterns: &List, rule: &str) -> Result<(), EvalError> {
+        let file = make::bind_name(targets, env, id);
+        let data = match fs::read(&file) {
+            Ok(data) => data,
+            Err(_) => return Ok(()),
+        };
+        for p in patterns.iter() {
+            if !self.patterns.contains_key(p) {
+                let regex = Regex::new(p).map_err(|e| EvalError::Parse(format!("HDRSCAN {}: {}", p, e)))?;
+                self.patterns.insert(p.to_string(), regex);
+            }
+        }
+        let mut headers = List::new();
+        for line in String::from_utf8_lossy(&data).lines() {
+            for p in patterns.iter() {
+                if let Some(header) = self.patterns[p].captures(line).and_then(|c| c.get(1)) {
+                    headers.append(header.as_str());
+                }
+            }
+        }
+        trace!(debug::SEARCH, "header scan of {}: {}", file, headers);
+        if headers.is_empty() {
+            return Ok(());
+        }
+        let name = targets.get(id).name().to_string();
+        targets.with_scope(&name, env, |targets, env| {
+            self.call(targets, env, rule, vec![List::from(vec![name.as_str()]), headers])
+        })?;
+        Ok(())
+    }
+
+    /// Invokes rule `name` with `args`, returning what it returns.  A rule
+    /// with the same name as an action also attaches the action to the
+    /// targets in its first argument, updating them from its second.
+    pub fn call(&mut self, targets: &mut Targets, env: &mut Environment, name: &str,
+                mut args: Vec<List>) -> Result<List, EvalError> {
+        while args.len() < 2 {
+            args.push(List::new());
+        }
+        trace!(debug::COMPILE, ">>>> {} {}", name,
+               args.iter().map(|a| a.to_string()).collect::<Vec<_>>().join(" : "));
+        let builtin = match self.rules.contains_key(name) {
+            true => None,
+            false => Builtin::find(name),
+        };
+        let known = builtin.is_some() || self.rules.contains_key(name) || self.actions.contains_key(name);
+        if let (Some(profile), true) = (self.profile.as_mut(), known) {
+            profile.enter(name, builtin.is_some());
+        }
+        let result = match builtin {
+            Some(builtin) => builtin.call(targets, &args),
+            None => self.invoke(targets, env, name, args),
+        };
+        if let (Some(profile), true) = (self.profile.as_mut(), known) {
+            profile.exit();
+        }
+        result
+    }
+
+    /// Runs rule `name`, which is not a builtin.
+    fn invoke(&mut self, targets: &mut Targets, env: &mut Environment, name: &str,
+              args: Vec<List>) -> Result<List, EvalError> {
+        if let Some(action) = self.actions.get(name) {
+            targets.invoke(action, &args[0], &args[1]);
+        }
+        let (params, body) = match self.rules.get(name) {
+            Some(rule) => (rule.params.clone(), rule.body.clone()),
+            None => {
+                if !self.actions.contains_key(name) {
+                    eprintln!("warning: unknown rule {}", name);
+                }
+                return Ok(List::new());
+            }
+        };
+
+        let mut locals = HashMap::new();
+        for (i, value) in args.iter().enumerate().take(9) {
+            locals.insert((i + 1).to_string(), Variable::new(value.clone()));
+        }
+        locals.insert("<".to_string(), Variable::new(args[0].clone()));
+        locals.insert(">".to_string(), Variable::new(args[1].clone()));
+        for (i, param) in params.iter().enumerate() {
+            let value = args.get(i).cloned().unwrap_or_default();
+            locals.insert(param.clone(), Variable::new(value));
+        }
+        let saved = env.push(&locals);
+        let flow = self.block(targets, env, &body);
+        env.pop(saved);
+        match flow? {
+            Flow::Return(value) => Ok(value),
+            _ => Ok(List::new()),
+        }
+    }
+
+    /// Runs a block, undoing its `local` declarations at the end.
+    fn block(&mut self, targets: &mut Targets, env: &mut Environment,
+             stmts: &[Stmt]) -> Result<Flow, EvalError> {
+        let mut locals: Vec<Saved> = Vec::new();
+        let mut flow = Ok(Flow::Normal);
+        for stmt in stmts {
+            flow = match *stmt {
+                Stmt::Local(ref names, ref value) => self.local(targets, env, names, value.as_ref())
+                    .map(|saved| { locals.push(saved); Flow::Normal }),
+                _ => self.exec(targets, env, stmt),
+            };
+            match flow {
+                Ok(Flow::Normal) => {}
+                _ => break,
+            }
+        }
+        while let Some(saved) = locals.pop() {
+            env.pop(saved);
+        }
+        flow
+    }
+
+    fn local(&mut self, targets: &mut Targets, env: &mut Environment, names: &[Arg],
+             value: Option<&Vec<Arg>>) -> Result<Saved, EvalError> {
+        let names = self.list(targets, env, names)?;
+        let value = match value {
+            Some(value) => self.list(targets, env, value)?,
+            None => List::new(),
+        };
+        let settings = names.iter().map(|n| (n.clone(), Variable::new(value.clone()))).collect();
+        Ok(env.push(&settings))
+    }
+
+    fn exec(&mut self, targets: &mut Targets, env: &mut Environment,
+            stmt: &Stmt) -> Result<Flow, EvalError> {
+        match *stmt {
+            Stmt::Block(ref stmts) => return self.block(targets, env, stmts),
+            Stmt::Include(ref names) => {
+                let names = self.list(targets, env, names)?;
+                if let Some(name) = names.get(0) {
+                    let id = targets.bind(name);
+                    let file = make::bind_name(targets, env, id);
+                    let data = fs::read(&file).map_err(|e| EvalError::Io(file.clone(), e))?;
+                    self.include(targets, env, &file, &data)?;
+                }
+            }
+            Stmt::Invoke(ref name, ref args) => {
+                let name = self.arg(targets, env, name)?;
+                let args = self.lol(targets, env, args)?;
+                self.call_list(targets, env, &name, args)?;
+            }
+            Stmt::Assign(ref names, op, ref value) => {
+                let names = self.arg(targets, env, names)?;
+                let value = self.list(targets, env, value)?;
+                for name in names.iter() {
+                    env.set(name, op, &value);
+                }
+            }
+            Stmt::AssignOn(ref names, ref on, op, ref value) => {
+                let names = self.arg(targets, env, names)?;
+                let on = self.list(targets, env, on)?;
+                let value = self.list(targets, env, value)?;
+                for name in names.iter() {
+                    targets.set_on(&on, name, op, &value);
+                }
+            }
+            Stmt::Local(..) => unreachable!("locals are declared by the enclosing block"),
+            Stmt::Break => return Ok(Flow::Break),
+            Stmt::Continue => return Ok(Flow::Continue),
+            Stmt::Return(ref value) => return Ok(Flow::Return(self.list(targets, env, value)?)),
+            Stmt::For(ref var, ref values, ref body) => {
+                let values = self.list(targets, env, values)?;
+                for value in values.iter() {
+                    env.set(var, Assign::Set, &List::from(vec![value.as_str()]));
+                    match self.block(targets, env, body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => {}
+                    }
+                }
+            }
+            Stmt::Switch(ref switch) => {
+                let value = self.list(targets, env, &switch.value)?;
+                if let Some(block) = switch.select(&value) {
+                    return self.block(targets, env, block);
+                }
+            }
+            Stmt::If(ref cond, ref body, ref other) => {
+                if self.cond(targets, env, cond)? {
+                    return self.block(targets, env, body);
+                }
+                if let Some(ref other) = *other {
+                    return self.exec(targets, env, other);
+                }
+            }
+            Stmt::While(ref cond, ref body) => {
+                while self.cond(targets, env, cond)? {
+                    match self.block(targets, env, body)? {
+                        Flow::Break => break,
+                        Flow::Return(value) => return Ok(Flow::Return(value)),
+                        _ => {}
+                    }
+                }
+            }
+            Stmt::Rule(ref decl) => {
+                let rule = Rule { params: decl.args.clone(), body: decl.body.clone() };
+                self.rules.insert(decl.name.clone(), rule);
+            }
+            Stmt::On(ref target, ref body) => {
+                let on = self.arg(targets, env, target)?;
+                if let Some(name) = on.get(0) {
+                    return targets.with_scope(name, env, |targets, env| self.exec(targets, env, body));
+                }
+            }
+            Stmt::Actions(ref decl) => {
+                let mut action = Action::new(decl.name.as_str(), decl.flags.clone(), decl.command.as_str());
+                action.bind_list = List::from(decl.bind_list.clone());
+                self.actions.insert(decl.name.clone(), Rc::new(action));
+            }
+        }
+        Ok(Flow::Normal)
+    }
+
+    /// Calls the rule named by the first word of `name`; any other words
+    /// are put before the first argument, as jam does.
+    fn call_list(&mut self, targets: &mut Targets, env: &mut Environment, name: &List,
+                 mut args: Vec<List>) -> Result<List, EvalError> {
+        let rule = match name.get(0) {
+            Some(rule) => rule.to_string(),
+            None => return Ok(List::new()),
+        };
+        if name.length() > 1 {
+            let mut first = name.sublist(1, name.length() - 1);
+            if let Some(rest) = args.first() {
+                first.extend(rest);
+            }
+            match args.first_mut() {
+                Some(a) => *a = first,
+                None => args.push(first),
+            }
+        }
+        self.call(targets, env, &rule, args)
+    }
+
+    fn arg(&mut self, targets: &mut Targets, env: &mut Environment,
+           arg: &Arg) -> Result<List, EvalError> {
+        match *arg {
+            Arg::Word(ref word) => Ok(expand::expand(word, env)),
+            Arg::Call(ref func) => self.func(targets, env, func),
+        }
+    }
+
+    fn list(&mut self, targets: &mut Targets, env: &mut Environment,
+            args: &[Arg]) -> Result<List, EvalError> {
+        let mut list = List::new();
+        for arg in args {
+            list.extend(&self.arg(targets, env, arg)?);
+        }
+        Ok(list)
+    }
+
+    fn lol(&mut self, targets: &mut Targets, env: &mut Environment,
+           lol: &[Vec<Arg>]) -> Result<Vec<List>, EvalError> {
+        lol.iter().map(|args| self.list(targets, env, args)).collect()
+    }
+
+    fn func(&mut self, targets: &mut Targets, env: &mut Environment,
+            func: &Func) -> Result<List, EvalError> {
+        match *func {
+            Func::Invoke(ref name, ref args) => {
+                let name = self.arg(targets, env, name)?;
+                let args = self.lol(targets, env, args)?;
+                self.call_list(targets, env, &name, args)
+            }
+            Func::On(ref target, ref name, ref args) => {
+                let on = self.arg(targets, env, target)?;
+                let on = match on.get(0) {
+                    Some(on) => on.to_string(),
+                    None => return Ok(List::new()),
+                };
+                targets.with_scope(&on, env, |targets, env| {
+                    let name = self.arg(targets, env, name)?;
+                    let args = self.lol(targets, env, args)?;
+                    self.call_list(targets, env, &name, args)
+                })
+            }
+            Func::OnReturn(ref target, ref value) => {
+                let on = self.arg(targets, env, target)?;
+                let on = match on.get(0) {
+                    Some(on) => on.to_string(),
+                    None => return Ok(List::new()),
+                };
+                targets.with_scope(&on, env, |targets, env| self.list(targets, env, value))
+            }
+        }
+    }
+
+    fn cond(&mut self, targets: &mut Targets, env: &mut Environment,
+            expr: &Expr) -> Result<bool, EvalError> {
+        Ok(match *expr {
+            Expr::Value(ref arg) => self.arg(targets, env, arg)?.get(0).is_some_and(|s| !s.is_empty()),
+            Expr::In(ref arg, ref list) => {
+                let values = self.arg(targets, env, arg)?;
+                let list = self.list(targets, env, list)?;
+                values.iter().all(|v| list.iter().any(|l| l == v))
+            }
+            Expr::Not(ref e) => !self.cond(targets, env, e)?,
+            Expr::Binary(BinaryOp::Boolean(BooleanOp::And), ref l, ref r) =>
+                self.cond(targets, env, l)? && self.cond(targets, env, r)?,
+            Expr::Binary(BinaryOp::Boolean(BooleanOp::Or), ref l, ref r) =>
+                self.cond(targets, env, l)? || self.cond(targets, env, r)?,
+            Expr::Binary(BinaryOp::Compare(op), ref l, ref r) => {
+                let l = self.operand(targets, env, l)?;
+                let r = self.operand(targets, env, r)?;
+                let order = compare(&l, &r);
+                match op {
+                    CompareOp::Equals => order == Ordering::Equal,
+                    CompareOp::NotEquals => order != Ordering::Equal,
+                    CompareOp::GreaterThan => order == Ordering::Greater,
+                    CompareOp::GreaterThanEquals => order != Ordering::Less,
+                    CompareOp::LessThan => order == Ordering::Less,
+                    CompareOp::LessThanEquals => order != Ordering::Greater,
+                }
+            }
+        })
+    }
+
+    /// The value compared by `=`, `<` and the like: that of an argument,
+    /// or for anything else "1" if it is true and nothing if not.
+    fn operand(&mut self, targets: &mut Targets, env: &mut Environment,
+               expr: &Expr) -> Result<List, EvalError> {
+        match *expr {
+            Expr::Value(ref arg) => self.arg(targets, env, arg),
+            _ => Ok(match self.cond(targets, env, expr)? {
+                true => List::from(vec!["1"]),
+                false => List::new(),
+            }),
+        }
+    }
+}
+
+/// Compares two lists word by word, a missing word being empty.
+fn compare(l: &List, r: &List) -> Ordering {
+    (0..l.length().max(r.length()))
+        .map(|i| l.get(i).unwrap_or("").cmp(r.get(i).unwrap_or("")))
+        .find(|&o| o != Ordering::Equal)
+        .unwrap_or(Ordering::Equal)
+}
+
+/// A rule jam itself provides.
+#[derive(Clone, Copy)]
+enum Builtin {
+    /// Sets a flag on the targets in the first argument.
+    Flag(Flags),
+    Depends,
+    Includes,
+    Echo,
+    Exit,
+    Glob,
+}
+
+impl Builtin {
+    /// The builtin rule `name`, if it is one.
+    fn find(name: &str) -> Option<Builtin> {
+        Some(match name {
+            "ALWAYS" | "Always" => Builtin::Flag(Flags::Always),
+            "LEAVES" | "Leaves" => Builtin::Flag(Flags::Leaves),
+            "NOCARE" | "NoCare" => Builtin::Flag(Flags::NoCare),
+            "NOTFILE" | "NotFile" | "NOTIME" => Builtin::Flag(Flags::NotFile),
+            "NOUPDATE" | "NoUpdate" => Builtin::Flag(Flags::NoUpdate),
+            "PRECIOUS" => Builtin::Flag(Flags::Precious),
+            "TEMPORARY" | "Temporary" => Builtin::Flag(Flags::Temporary),
+            "DEPENDS" | "Depends" => Builtin::Depends,
+            "INCLUDES" | "Includes" => Builtin::Includes,
+            "ECHO" | "Echo" => Builtin::Echo,
+            "EXIT" | "Exit" => Builtin::Exit,
+            "GLOB" => Builtin::Glob,
+            _ => return None,
+        })
+    }
+
+    fn call(self, targets: &mut Targets, args: &[List]) -> Result<List, EvalError> {
+        match self {
+            Builtin::Flag(flag) => {
+                for name in args[0].iter() {
+                    let id = targets.bind(name);
+                    let t = targets.get_mut(id);
+                    if !t.has(flag) {
+                        t.flags.push(flag);
+                    }
+                }
+            }
+            Builtin::Depends => targets.add_edges(&args[0], &args[1], Edge::Depends),
+            Builtin::Includes => targets.add_edges(&args[0], &args[1], Edge::Includes),
+            Builtin::Echo => println!("{}", args[0]),
+            Builtin::Exit => return Err(EvalError::Exit(args[0].to_string())),
+            Builtin::Glob => return Ok(glob_dirs(&args[0], &args[1])),
+        }
+        Ok(List::new())
+    }
+}
+
+/// The files in `dirs` whose names match any of `patterns`, for `GLOB`.
+fn glob_dirs(dirs: &List, patterns: &List) -> List {
+    let mut found = List::new();
+    for dir in dirs.iter() {
+        let mut names: Vec<String> = match fs::read_dir(dir) {
+            Ok(entries) => entries.filter_map(|e| e.ok())
+                .map(|e| e.file_name().to_string_lossy().into_owned())
+                .filter(|name| patterns.iter().any(|p| glob::matches(p, name)))
+                .collect(),
+            Err(_) => continue,
+        };
+        names.sort();
+        for name in names {
+            found.append(Path::new(dir).join(name).to_string_lossy());
+        }
+    }
+    found
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+
+    fn run(text: &str) -> (Targets, Environment<'static>) {
+        let mut targets = Targets::new();
+        let mut env = Environment::new();
+        Interpreter::new().include(&mut targets, &mut env, "Jamfile", text.as_bytes()).unwrap();
+        (targets, env)
+    }
+
+    fn value(env: &Environment, name: &str) -> String {
+        env[name].value().to_string()
+    }
+
+    #[test]
+    fn switch_runs_the_first_matching_case() {
+        let (_, env) = run("
+            for f in main.c util.h README {
+                switch $(f) {
+                    case *.c : KIND += source ;
+                    case *.[ch] : KIND += header ;
+                    case * : KIND += other ;
+                }
+            }");
+        assert_eq!(value(&env, "KIND"), "source header other");
+    }
+
+    #[test]
+    fn rules_see_their_arguments_and_locals_end_with_the_block() {
+        let (_, env) = run("
+            X = outer ;
+            rule Join prefix : words {
+                local X = $(prefix)$(words) ;
+                return $(X) $(<) $(2) ;
+            }
+            Y = [ Join x : a b ] ;
+            { local X = inner ; Z = $(X) ; }");
+        assert_eq!(value(&env, "Y"), "xa xb x a b");
+        assert_eq!(value(&env, "Z"), "inner");
+        assert_eq!(value(&env, "X"), "outer");
+    }
+
+    #[test]
+    fn conditions_and_loops() {
+        let (_, env) = run("
+            A = 1 ; B = 2 ;
+            if $(A) = 1 && ! $(UNSET) { R += and ; }
+            if $(A) > $(B) { R += gt ; } else if b in a b { R += member ; }
+            for i in 1 2 3 4 {
+                if $(i) = 2 { continue ; }
+                if $(i) = 4 { break ; }
+                R += $(i) ;
+            }");
+        assert_eq!(value(&env, "R"), "and member 1 3");
+    }
+
+    #[test]
+    fn builtins_build_the_graph() {
+        let (targets, _) = run("
+            rule Object { DEPENDS $(<) : $(>) ; INCLUDES $(>) : $(3) ; }
+            Object main.o : main.c : main.h ;
+            NOTFILE all ;
+            DEPENDS all : main.o ;");
+        let id = |name| targets.find(name).unwrap();
+        assert!(targets.get(id("all")).has(Flags::NotFile));
+        assert_eq!(targets.graph().edges(id("all"), Edge::Depends), &[id("main.o")]);
+        assert_eq!(targets.graph().edges(id("main.o"), Edge::Depends), &[id("main.c")]);
+        assert_eq!(targets.graph().edges(id("main.c"), Edge::Includes), &[id("main.h")]);
+    }
+
+    #[test]
+    fn actions_and_builtins_build_the_graph() {
+        let (targets, _) = run("
+            actions quietly Cc bind HDRS { cc -c -o $(<) $(>) }
+            rule Object { DEPENDS $(<) : $(>) ; Cc $(<) : $(>) ; }
+            Object main.o : main.c ;
+            CFLAGS on main.o = -O2 ;
+            NOTFILE all ;
+            DEPENDS all : main.o ;
+            X = [ on main.o return $(CFLAGS) ] ;");
+        let obj = targets.get(targets.find("main.o").unwrap());
+        assert_eq!(obj.actions.len(), 1);
+        assert_eq!(obj.actions[0].action.command, " cc -c -o $(<) $(>) ");
+        assert_eq!(obj.actions[0].action.bind_list, List::from(vec!["HDRS"]));
+        assert_eq!(obj.var("CFLAGS").unwrap().value(), &List::from(vec!["-O2"]));
+        assert!(targets.get(targets.find("all").unwrap()).has(Flags::NotFile));
+        let all = targets.find("all").unwrap();
+        assert_eq!(make::dependencies(&targets, all), vec![targets.find("main.o").unwrap()]);
+    }
+
+    #[test]
+    fn on_target_sees_its_variables() {
+        let (_, env) = run("
+            V on t = target ;
+            V = global ;
+            on t { X = $(V) ; }
+            Y = [ on t return $(V) ] ;
+            rule Get { return $(V) ; }
+            Z = [ on t Get ] ;");
+        assert_eq!(value(&env, "X"), "target");
+        assert_eq!(value(&env, "Y"), "target");
+        assert_eq!(value(&env, "Z"), "target");
+        assert_eq!(value(&env, "V"), "global");
+    }
+
+    #[test]
+    fn include_and_exit() {
+        let dir = TempDir::new("eval-include");
+        let sub = dir.write("sub.jam", "FROM = sub ;\n");
+        let text = format!("include {} ;\nEXIT stop here ;\nAFTER = 1 ;", sub.display());
+        let mut targets = Targets::new();
+        let mut env = Environment::new();
+        let mut jam = Interpreter::new();
+        let err = jam.include(&mut targets, &mut env, "Jamfile", text.as_bytes()).unwrap_err();
+        assert_eq!(err.to_string(), "stop here");
+        assert_eq!(value(&env, "FROM"), "sub");
+        assert!(env.get("AFTER").is_none());
+
+        let err = jam.include(&mut targets, &mut env, "Jamfile", b"X = ( ;").unwrap_err();
+        assert!(err.to_string().starts_with("Jamfile:1:"), "{}", err);
+    }
+
+    #[test]
+    fn rule_calls_profiled() {
+        let mut levels = debug::Levels::none();
+        levels.apply("+10").unwrap();
+        let out = debug::capture(levels, || {
+            let mut jam = Interpreter::new();
+            let text = "rule Inner { DEPENDS $(1) : x ; } rule Outer { Inner a ; Inner b ; } Outer ;";
+            jam.include(&mut Targets::new(), &mut Environment::new(), "Jamfile", text.as_bytes()).unwrap();
+            jam.trace();
+        });
+        let calls = |rule: &str| out.lines().find(|l| l.ends_with(rule)).map(|l| l.split_whitespace().nth(2).unwrap().to_string());
+        assert_eq!(calls("  Outer").as_deref(), Some("1"));
+        assert_eq!(calls("  Inner").as_deref(), Some("2"));
+        assert_eq!(calls("  DEPENDS (builtin)").as_deref(), Some("2"));
+        assert_eq!(debug::capture(debug::Levels::none(), || Interpreter::new().trace()), "");
+    }
+
+    #[test]
+    fn headers_scanned_and_profiled() {
+        let dir = TempDir::new("eval-headers");
+        dir.write("main.c", "#include \"a.h\"\nint main;\n");
+        dir.write("a.h", "# include <b.h>\n");
+        dir.write("b.h", "");
+        let mut targets = Targets::new();
+        let mut env = Environment::new();
+        let set = |env: &mut Environment, name: &str, value: &str| env.set(name, Assign::Set, &List::from(vec![value]));
+        set(&mut env, "SEARCH", dir.path().to_str().unwrap());
+        set(&mut env, "HDRSCAN", r#"^#[ ]*include[ ]*[<"]([^">]*)[">]"#);
+        set(&mut env, "HDRRULE", "HdrRule");
+
+        ::profile::start();
+        let mut jam = Interpreter::new();
+        let text = "rule HdrRule { INCLUDES $(<) : $(>) ; } DEPENDS prog : main.c ;";
+        jam.include(&mut targets, &mut env, "Jamfile", text.as_bytes()).unwrap();
+        jam.scan_headers(&mut targets, &mut env, &List::from(vec!["prog"])).unwrap();
+        let events = ::profile::finish().unwrap().events;
+
+        let includes = |name: &str| targets.graph().edges(targets.find(name).unwrap(), Edge::Includes)
+            .iter().map(|&h| targets.get(h).name().to_string()).collect::<Vec<_>>();
+        assert_eq!(includes("main.c"), vec!["a.h"]);
+        assert_eq!(includes("a.h"), vec!["b.h"]);
+        let spans: Vec<(&str, &str)> = events.iter().map(|e| (e.category, e.name.as_str())).collect();
+        // Each span is recorded as it ends, after those inside it.
+        assert_eq!(spans, vec![("parse", "Jamfile"), ("rule", "DEPENDS"), ("eval", "Jamfile"), ("scan", "prog"),
+                               ("rule", "INCLUDES"), ("rule", "HdrRule"), ("scan", "main.c"),
+                               ("rule", "INCLUDES"), ("rule", "HdrRule"), ("scan", "a.h"), ("scan", "b.h")]);
+    }
+
+    #[test]
+    fn jambase_runs() {
+        let mut targets = Targets::new();
+        let mut env = Environment::new();
+        env.set("UNIX", Assign::Set, &List::from(vec!["true"]));
+        env.set("OS", Assign::Set, &List::from(vec!["LINUX"]));
+        let mut jam = Interpreter::new();
+        env.set("JAMFILE", Assign::Set, &List::from(vec!["Jamfile.none"]));
+        let r = jam.include(&mut targets, &mut env, "Jambase", ::jambase::data());
+        match r {
+            Err(EvalError::Io(ref file, _)) => assert_eq!(file, "Jamfile.none"),
+            r => panic!("{:?}", r),
+        }
+        assert!(targets.find("all").is_some());
+        assert_eq!(value(&env, "SUFOBJ"), ".o");
+    }
+}
@@ -1,12 +1,37 @@
+use lalrpop_util::ParseError;
+use lexer;
+
 mod ast;
 mod compiler;
+mod eval;
 mod grammar;
 mod grammar2;
+mod profile;
 mod rule;
 
+pub use self::eval::{EvalError, Interpreter};
+
+/// Parses the statements of a Jamfile.  Errors name the file and line.
+pub fn parse(file: &str, data: &[u8]) -> Result<Vec<ast::Stmt>, String> {
+    let mut t = lexer::Tokenizer::new();
+    t.include_data(data);
+    let line = |at: usize| data[..at.min(data.len())].iter().filter(|&&b| b == b'\n').count() + 1;
+    grammar::parse_run(t).map_err(|e| match e {
+        ParseError::InvalidToken { location } =>
+            format!("{}:{}: invalid token", file, line(location)),
+        ParseError::UnrecognizedToken { token: Some((at, tok, _)), .. } =>
+            format!("{}:{}: syntax error at {:?}", file, line(at), tok),
+        ParseError::UnrecognizedToken { token: None, .. } =>
+            format!("{}:{}: unexpected end of file", file, line(data.len())),
+        ParseError::ExtraToken { token: (at, tok, _) } =>
+            format!("{}:{}: syntax error at {:?}", file, line(at), tok),
+        ParseError::User { error } =>
+            format!("{}:{}: {:?}", file, line(error.location), error.code),
+    })
+}
+
 #[cfg(test)]
 mod tests {
-    use lexer;
     use super::*;
 
     #[test]
@@ -38,6 +63,32 @@ mod tests {
         assert!(grammar2::parse_statement(tokenized("Foo ;")).is_ok());
     }
 
+    #[test]
+    fn switch_select_test() {
+        use list::List;
+        let case = |p: &str, b: u32| ast::Case { pattern: p.to_string(), block: b };
+        let mut value = List::new();
+        value.append("main.c");
+        value.append("other.h");
+        let s = ast::SwitchStmt {
+            value: vec![],
+            cases: vec![case("*.h", 1), case("*.[ch]", 2), case("*", 3)],
+        };
+        assert_eq!(s.select(&value), Some(&2));
+
+        let s = ast::SwitchStmt { value: vec![], cases: vec![case("?*", 1), case("", 2)] };
+        assert_eq!(s.select(&List::new()), Some(&2));
+
+        let s = ast::SwitchStmt { value: vec![], cases: vec![case("?", 1)] };
+        assert_eq!(s.select(&List::new()), None);
+    }
+
+    #[test]
+    fn parse_jambase() {
+        let r = parse("Jambase", ::jambase::data());
+        assert!(r.is_ok(), "{:?}", r.err());
+    }
+
     fn tokenized(s: &str) -> lexer::Tokenizer {
         let mut t = lexer::Tokenizer::new();
         let bytes: Vec<u8> = s.bytes().collect();
@@ -0,0 +1,161 @@
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use debug;
+use profile;
+
+/// The calls made to one rule or builtin.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Entry {
+    pub builtin: bool,
+    pub calls: usize,
+    /// Time spent in the rule including the rules it called.
+    pub gross: Duration,
+    /// Time spent in the rule itself.
+    pub net: Duration,
+}
+
+struct Frame {
+    name: String,
+    start: Instant,
+    /// Time spent in the rules this one called.
+    children: Duration,
+    /// Whether an outer call of the same rule is still running, in which
+    /// case its gross time already covers this one.
+    recursive: bool,
+}
+
+/// Counts the calls to each rule and builtin while Jamfiles are evaluated
+/// and how long they take, for `-d+10`.  The evaluator calls `enter` as a
+/// rule starts and `exit` as it returns.  Each call also shows in a
+/// `--profile` timeline.
+pub struct RuleProfile {
+    entries: HashMap<String, Entry>,
+    stack: Vec<Frame>,
+    /// Where the time is read from, the system clock but for in tests.
+    clock: Box<dyn Fn() -> Instant>,
+}
+
+impl Default for RuleProfile {
+    fn default() -> RuleProfile {
+        RuleProfile { entries: HashMap::new(), stack: Vec::new(), clock: Box::new(Instant::now) }
+    }
+}
+
+impl RuleProfile {
+    pub fn new() -> RuleProfile { RuleProfile::default() }
+
+    #[cfg(test)]
+    fn with_clock<F: Fn() -> Instant + 'static>(clock: F) -> RuleProfile {
+        RuleProfile { clock: Box::new(clock), ..RuleProfile::default() }
+    }
+
+    pub fn enter(&mut self, name: &str, builtin: bool) {
+        let entry = self.entries.entry(name.to_string()).or_default();
+        entry.builtin = builtin;
+        entry.calls += 1;
+        let recursive = self.stack.iter().any(|f| f.name == name);
+        let start = (self.clock)();
+        self.stack.push(Frame { name: name.to_string(), start, children: Duration::ZERO, recursive });
+    }
+
+    /// Ends the innermost call in progress.
+    pub fn exit(&mut self) {
+        let frame = match self.stack.pop() {
+            Some(f) => f,
+            None => return,
+        };
+        let elapsed = (self.clock)() - frame.start;
+        profile::record("rule", &frame.name, frame.start, 0);
+        if let Some(parent) = self.stack.last_mut() {
+            parent.children += elapsed;
+        }
+        let entry = self.entries.get_mut(&frame.name).unwrap();
+        if !frame.recursive {
+            entry.gross += elapsed;
+        }
+        entry.net += elapsed.saturating_sub(frame.children);
+    }
+
+    #[cfg(test)]
+    pub fn get(&self, name: &str) -> Option<&Entry> {
+        self.entries.get(name)
+    }
+
+    /// Writes a table of the rules called, the most expensive first.
+    pub fn report(&self, out: &mut dyn Write) -> io::Result<()> {
+        let mut rules: Vec<(&String, &Entry)> = self.entries.iter().collect();
+        rules.sort_by(|a, b| b.1.net.cmp(&a.1.net).then_with(|| a.0.cmp(b.0)));
+        writeln!(out, "{:>10} {:>10} {:>8}  rule", "gross", "net", "calls")?;
+        for (name, e) in rules {
+            writeln!(out, "{:>10.6} {:>10.6} {:>8}  {}{}", e.gross.as_secs_f64(), e.net.as_secs_f64(),
+                     e.calls, name, if e.builtin { " (builtin)" } else { "" })?;
+        }
+        Ok(())
+    }
+
+    /// Prints the report as debug output if `-d+10` is on.
+    pub fn trace(&self) {
+        if debug::enabled(debug::PROFILE) {
+            let mut out = Vec::new();
+            let _ = self.report(&mut out);
+            debug::write(format_args!("{}", String::from_utf8_lossy(&out)));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+    use std::rc::Rc;
+    use std::str;
+
+    #[test]
+    fn nested_calls() {
+        let base = Instant::now();
+        let now = Rc::new(Cell::new(Duration::ZERO));
+        let clock = now.clone();
+        let mut p = RuleProfile::with_clock(move || base + clock.get());
+        let advance = |millis| now.set(now.get() + Duration::from_millis(millis));
+
+        p.enter("SubDir", false);
+        advance(1);
+        p.enter("FDirName", false);
+        advance(2);
+        p.enter("FDirName", false);
+        p.enter("ECHO", true);
+        advance(5);
+        p.exit();
+        p.exit();
+        advance(1);
+        p.exit();
+        advance(1);
+        p.exit();
+        p.exit();
+
+        let ms = Duration::from_millis;
+        let sub = p.get("SubDir").unwrap();
+        let dir = p.get("FDirName").unwrap();
+        let echo = p.get("ECHO").unwrap();
+        assert_eq!((sub.calls, dir.calls, echo.calls), (1, 2, 1));
+        assert!(echo.builtin && !sub.builtin);
+        assert_eq!((echo.gross, echo.net), (ms(5), ms(5)));
+        assert_eq!((dir.gross, dir.net), (ms(8), ms(3)));
+        assert_eq!((sub.gross, sub.net), (ms(10), ms(2)));
+
+        let mut out = Vec::new();
+        p.report(&mut out).unwrap();
+        let out = str::from_utf8(&out).unwrap();
+        assert_eq!(out, "     gross        net    calls  rule
+  0.005000   0.005000        1  ECHO (builtin)
+  0.008000   0.003000        2  FDirName
+  0.010000   0.002000        1  SubDir
+");
+
+        let mut levels = debug::Levels::none();
+        levels.apply("+10").unwrap();
+        assert_eq!(debug::capture(levels, || p.trace()), out);
+        assert_eq!(debug::capture(debug::Levels::default(), || p.trace()), "");
+    }
+}
@@ -1,3 +1,10 @@
+use std::rc::Rc;
+use super::ast::Stmt;
+
+/// A rule declared with `rule Name params { ... }`.
 pub struct Rule {
-    arg_count: u8
-}
\ No newline at end of file
+    /// The names bound to the rule's arguments, besides `$(<)`, `$(>)`
+    /// and `$(1)` to `$(9)`.
+    pub params: Vec<String>,
+    pub body: Rc<Vec<Stmt>>,
+}
@@ -8,35 +8,10 @@ use self::LexerErrorCode::*;
 mod helpers {
     pub fn is_quote(b: u8) -> bool {
         let c = char::from(b);
-        c == '\'' || c == '"'
+        c == '"'
     }
 
     pub fn is_whitespace(b: u8) -> bool { char::from(b).is_whitespace() }
-
-    pub fn is_not_ws(b: u8) -> bool { return !is_whitespace(b) }
-
-    pub fn is_ident_start(b: u8) -> bool {
-        match char::from(b) {
-            '_' | '$' => true,
-            'a'...'z' => true,
-            'A'...'Z' => true,
-            '0'...'9' => true,
-            '/' | '\\' => true,
-            '.' | '-' => true,
-            '*' => true,
-            _ => false
-        }
-    }
-
-    pub fn is_ident_continue(b: u8) -> bool {
-        match char::from(b) {
-            '(' | ')' => true,
-            '+' | ',' => true,
-            '=' => true,
-            _ if is_ident_start(b) => true,
-            _ => false
-        }
-    }
 }
 
 
@@ -92,7 +67,30 @@ pub enum Token {
     While,
 }
 
+/// Every word jam gives a meaning to.  Like jam, punctuation is only
+/// recognised as a whole word, so `(w:e)` and `[]` are ordinary words.
 const KEYWORDS: &'static [(&'static str, Token)] = &[
+    ("!", Token::Bang),
+    ("!=", Token::BangEquals),
+    ("&", Token::Amper),
+    ("&&", Token::AmperAmper),
+    ("(", Token::LeftParen),
+    (")", Token::RightParen),
+    ("+=", Token::PlusEquals),
+    (":", Token::Colon),
+    (";", Token::SemiColon),
+    ("<", Token::LeftAngle),
+    ("<=", Token::LeftAngleEquals),
+    ("=", Token::Equals),
+    (">", Token::RightAngle),
+    (">=", Token::RightAngleEquals),
+    ("?=", Token::QuestionEquals),
+    ("[", Token::LeftBrace),
+    ("]", Token::RightBrace),
+    ("{", Token::LeftBracket),
+    ("|", Token::Bar),
+    ("||", Token::BarBar),
+    ("}", Token::RightBracket),
     ("actions", Token::Actions),
     ("bind", Token::Bind),
     ("break", Token::Break),
@@ -277,7 +275,11 @@ impl io::Seek for DataReader {
 
 #[derive(Clone, Eq, Ord, PartialEq, PartialOrd)]
 enum TokenizerMode {
+    /// After `actions`, up to the `{` opening the command text.
     Action,
+    /// In the command text, which is taken verbatim up to the `}` that
+    /// balances the opening one.
+    ActionBody,
     Normal
 }
 
@@ -303,67 +305,88 @@ impl Tokenizer {
         self.lookahead
     }
 
-    fn action(&mut self, idx0: usize, start: u8, end: u8) -> Spanned<Token, usize, LexerError> {
+    fn action(&mut self, idx0: usize) -> Spanned<Token, usize, LexerError> {
         let mut balance = 1;
-        loop {
-            if let Some((idx, b)) = self.advance() {
-                if b == b'\"' || b == b'\'' {
-                    self.advance();
-                    try!(self.string_literal(idx, b));
-                    continue;
-                } else if b == start {
-                    balance += 1;
-                } else if b == end {
-                    balance -= 1;
-                    if balance == 0 {
-                        debug_assert!(balance == 0);
-                        self.lookahead = Some((idx, b));
-                        self.mode = TokenizerMode::Normal;
-                        return Ok((idx0, Token::ActionString(self.buffer.slice_string(idx0, idx)), idx));
-                    }
+        let mut next = self.lookahead;
+        while let Some((idx, b)) = next {
+            if b == b'{' {
+                balance += 1;
+            } else if b == b'}' {
+                balance -= 1;
+                if balance == 0 {
+                    // The closing `}` is left for the normal scanner.
+                    self.mode = TokenizerMode::Normal;
+                    return Ok((idx0, Token::ActionString(self.buffer.slice_string(idx0, idx)), idx));
                 }
-            } else if balance > 0 {
-                return LexerError::create(UnterminatedCodeBlock, idx0);
-            } else {
-                debug_assert!(balance == 0);
-                self.mode = TokenizerMode::Normal;
-                return Ok((idx0, Token::ActionString(self.buffer.slice_string(idx0, self.buffer.len())), self.buffer.len()));
             }
+            next = self.advance();
         }
+        LexerError::create(UnterminatedCodeBlock, idx0)
     }
 
     fn action_scanner(&mut self) -> Option<Spanned<Token, usize, LexerError>> {
-        loop {
+        if self.mode == TokenizerMode::ActionBody {
             return match self.lookahead {
-                Some((idx0, b'{')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::LeftBracket, idx0+1)))
-                },
-                Some((idx0, b'}')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::RightBracket, idx0+1)))
-                },
-                Some((idx0, _)) => {
-                    Some(self.action(idx0, b'{', b'}'))
-                },
-                None => {
-                    Some(LexerError::create(UnrecognizedToken, self.buffer.index()))
-                }
-            }
+                Some((idx0, _)) => Some(self.action(idx0)),
+                None => Some(LexerError::create(UnterminatedCodeBlock, self.buffer.index())),
+            };
         }
+        // The modifiers, name and bind list are ordinary tokens.
+        let token = self.normal_scanner();
+        if let Some(Ok((_, Token::LeftBracket, _))) = token {
+            self.mode = TokenizerMode::ActionBody;
+        }
+        token
     }
 
+    /// Scans one whitespace separated word.  Double quotes may quote any
+    /// part of it and a backslash escapes the next character; either makes
+    /// the word plain text rather than a keyword.
     fn ident(&mut self, idx0: usize) -> Spanned<Token, usize, LexerError> {
-        let (start, word, end) = self.word(idx0);
-        let token = KEYWORDS.iter()
+        let mut word = Vec::new();
+        let mut quoted = false;
+        let mut literal = false;
+        let mut end = self.buffer.len();
+        while let Some((idx, b)) = self.lookahead {
+            if !quoted && is_whitespace(b) {
+                end = idx;
+                break;
+            }
+            if b == b'\\' {
+                literal = true;
+                match self.advance() {
+                    Some((_, b)) => word.push(b),
+                    None => break,
+                }
+            } else if is_quote(b) {
+                literal = true;
+                quoted = !quoted;
+            } else {
+                word.push(b);
+            }
+            self.advance();
+        }
+        if quoted {
+            return LexerError::create(UnterminatedStringLiteral, idx0);
+        }
+        let word = String::from_utf8_lossy(&word).into_owned();
+        let token = if literal {
+            if self.buffer.slice(idx0, idx0 + 1) == b"\"" {
+                Token::StringLiteral(word)
+            } else {
+                Token::Ident(word)
+            }
+        } else {
+            KEYWORDS.iter()
                 .filter(|&&(w, _)| w == word)
                 .map(|&(_, ref t)| t.clone())
                 .next()
                 .unwrap_or_else(|| {
-                    Token::Ident(word.to_string())
-                });
-        if token == Token::Actions { self.mode == TokenizerMode::Action; }
-        Ok((start, token.clone(), end))
+                    Token::Ident(word)
+                })
+        };
+        if token == Token::Actions { self.mode = TokenizerMode::Action; }
+        Ok((idx0, token, end))
     }
 
     pub fn include_data(&mut self, buffer: &[u8]) {
@@ -385,109 +408,6 @@ impl Tokenizer {
     fn normal_scanner(&mut self) -> Option<Spanned<Token, usize, LexerError>> {
         loop {
             return match self.lookahead {
-                Some((idx0, b'&')) => {
-                    match self.advance() {
-                        Some((idx1, b'&')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::AmperAmper, idx1+1)))
-                        },
-                        _ => Some(Ok((idx0, Token::Amper, idx0+1)))
-                    }
-                },
-                Some((idx0, b'|')) => {
-                    match self.advance() {
-                        Some((idx1, b'|')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::BarBar, idx1+1)))
-                        },
-                        _ => Some(Ok((idx0, Token::Bar, idx0+1)))
-                    }
-                },
-                Some((idx0, b'!')) => {
-                    match self.advance() {
-                        Some((idx1, b'=')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::BangEquals, idx1+1)))
-                        },
-                        _ => Some(Ok((idx0, Token::Bang, idx0+1)))
-                    }
-                },
-                Some((idx0, b'<')) => {
-                    match self.advance() {
-                        Some((idx1, b'=')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::LeftAngleEquals, idx1+1)))
-                        },
-                        _ => Some(Ok((idx0, Token::LeftAngle, idx0+1)))
-                    }
-                },
-                Some((idx0, b'>')) => {
-                    match self.advance() {
-                        Some((idx1, b'=')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::RightAngleEquals, idx1+1)))
-                        },
-                        _ => Some(Ok((idx0, Token::RightAngle, idx0+1)))
-                    }
-                },
-                Some((idx0, b'?')) => {
-                    match self.advance() {
-                        Some((idx1, b'=')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::QuestionEquals, idx1+1)))
-                        },
-                        _ => Some(LexerError::create(UnexpectedToken, idx0))
-                    }
-                },
-                Some((idx0, b'+')) => {
-                    match self.advance() {
-                        Some((idx1, b'=')) => {
-                            self.advance();
-                            Some(Ok((idx0, Token::PlusEquals, idx1+1)))
-                        },
-                        _ => Some(LexerError::create(UnexpectedToken, idx0))
-                    }
-                },
-                Some((idx0, b'(')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::LeftParen, idx0+1)))
-                },
-                Some((idx0, b')')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::RightParen, idx0+1)))
-                },
-                Some((idx0, b'{')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::LeftBracket, idx0+1)))    
-                },
-                Some((idx0, b'}')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::RightBracket, idx0+1)))
-                },
-                Some((idx0, b'[')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::LeftBrace, idx0+1)))
-                },
-                Some((idx0, b']')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::RightBrace, idx0+1)))
-                },
-                Some((idx0, b';')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::SemiColon, idx0+1)))
-                },
-                Some((idx0, b':')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::Colon, idx0+1)))
-                },
-                Some((idx0, b'=')) => {
-                    self.advance();
-                    Some(Ok((idx0, Token::Equals, idx0+1)))
-                },
-                Some((idx, b)) if is_quote(b) => {
-                    self.advance();
-                    Some(self.string_literal(idx, b))
-                },
                 Some((_, b'#')) => {
                     self.take_until_and_consume(|b| b == b'\n');
                     continue;
@@ -496,11 +416,8 @@ impl Tokenizer {
                     self.advance();
                     continue;
                 },
-                Some((idx0, b)) if is_ident_start(b) => {
-                    Some(self.ident(idx0))
-                },
                 Some((idx0, _)) => {
-                    Some(LexerError::create(UnrecognizedToken, idx0))
+                    Some(self.ident(idx0))
                 },
                 None => None
             }
@@ -509,39 +426,6 @@ impl Tokenizer {
 
     pub fn seek(&mut self, new_idx: usize) { self.buffer.set_index(new_idx); }
 
-    fn string_literal(&mut self, idx0: usize, delim: u8) -> Spanned<Token, usize, LexerError> {
-        let mut escape = false;
-        let term = |b: u8| {
-            if escape {
-                escape = false;
-                false
-            } else if b == b'\\' {
-                escape = true;
-                false
-            } else if b == delim {
-                true
-            } else {
-                false
-            }
-        };
-        match self.take_until(term) {
-            Some(idx1) => {
-                self.advance(); // consume the closing delimiter.
-                let s = self.buffer.slice_string(idx0+1, idx1);
-                Ok((idx0, Token::StringLiteral(s), idx1+1))
-            },
-            None => {
-                LexerError::create(UnterminatedStringLiteral, idx0)
-            }
-        }
-    }
-
-    fn take_while<F>(&mut self, mut keep_going: F) -> Option<usize>
-        where F: FnMut(u8) -> bool
-    {
-        self.take_until(|b| !keep_going(b))
-    }
-
     fn take_until<F>(&mut self, mut terminate: F) -> Option<usize>
         where F: FnMut(u8) -> bool
     {
@@ -562,20 +446,13 @@ impl Tokenizer {
             self.advance().map(|p| {p.0})
         })
     }
-
-    fn word(&mut self, idx0: usize) -> (usize, String, usize) {
-        match self.take_while(is_not_ws) {
-            Some(idx1) => (idx0, self.buffer.slice_string(idx0, idx1), idx1),
-            None => (idx0, self.buffer.slice_string(idx0, self.buffer.len()), self.buffer.len())
-        }
-    }
 }
 
 impl Iterator for Tokenizer {
     type Item = Spanned<Token, usize, LexerError>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        if self.mode == TokenizerMode::Action {
+        if self.mode != TokenizerMode::Normal {
             return self.action_scanner();
         }
         return self.normal_scanner()
@@ -50,6 +50,21 @@ fn test_tokenizer_actionstring() {
     assert_token(t.next(), Some(Ok((14, Token::RightBracket, 15))));
 }
 
+#[test]
+fn test_tokenizer_actions_definition() {
+    let mut t = Tokenizer::new();
+    t.include_data(b"actions quietly Cc bind HDRS { cc -c ${X} $(>) }\nx ;");
+    assert_token(t.next(), Some(Ok((0, Token::Actions, 7))));
+    assert_token(t.next(), Some(Ok((8, Token::Quietly, 15))));
+    assert_token(t.next(), Some(Ok((16, Token::Ident("Cc".to_string()), 18))));
+    assert_token(t.next(), Some(Ok((19, Token::Bind, 23))));
+    assert_token(t.next(), Some(Ok((24, Token::Ident("HDRS".to_string()), 28))));
+    assert_token(t.next(), Some(Ok((29, Token::LeftBracket, 30))));
+    assert_token(t.next(), Some(Ok((30, Token::ActionString(" cc -c ${X} $(>) ".to_string()), 47))));
+    assert_token(t.next(), Some(Ok((47, Token::RightBracket, 48))));
+    assert_token(t.next(), Some(Ok((49, Token::Ident("x".to_string()), 50))));
+}
+
 #[test]
 fn test_tokenizer_comment() {
     let mut t = Tokenizer::new();
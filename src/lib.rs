@@ -1,19 +1,47 @@
 extern crate jam_depgraph;
+extern crate lalrpop_util;
+extern crate libc;
 #[macro_use]
 extern crate nom;
+extern crate regex;
+
+#[macro_use]
+mod debug;
 
 mod action;
+mod cache;
 mod calculator1;
 
-mod env;
-mod jambase;
-mod lang;
+mod command;
+mod compdb;
+pub mod env;
+pub mod execute;
+mod expand;
+mod export;
+mod glob;
+pub mod graph;
+mod hash;
+mod interrupt;
+pub mod jambase;
+mod jobserver;
+mod json;
+pub mod lang;
 mod lexer;
-mod list;
+pub mod list;
+pub mod make;
+pub mod options;
 mod path;
+mod profile;
+mod report;
 mod rule;
+mod schedule;
+mod script;
+mod state;
 mod target;
-mod variable;
+#[cfg(test)]
+mod testing;
+pub mod variable;
+mod why;
 
 #[test]
 fn it_works() {
@@ -1,8 +1,10 @@
-#[derive(Clone, Debug, PartialEq)]
+use std::fmt;
+
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct List(Vec<String>);
 
 impl List {
-    pub fn new() -> List {
+    pub const fn new() -> List {
         List(Vec::new())
     }
 
@@ -10,6 +12,10 @@ impl List {
         self.0.push(s.to_string());
     }
 
+    pub fn extend(&mut self, other: &List) {
+        self.0.extend_from_slice(&other.0);
+    }
+
     pub fn expand(&self) -> List {
         unimplemented!();
     }
@@ -18,6 +24,10 @@ impl List {
         self.0.get(idx).map(|s| s.as_str())
     }
 
+    pub fn is_empty(&self) -> bool { self.0.is_empty() }
+
+    pub fn iter(&self) -> ::std::slice::Iter<'_, String> { self.0.iter() }
+
     pub fn length(&self) -> usize { self.0.len() }
 
     pub fn print(&self) {
@@ -35,6 +45,24 @@ impl List {
     }
 }
 
+impl fmt::Display for List {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.0.join(" "))
+    }
+}
+
+impl<'a> From<Vec<&'a str>> for List {
+    fn from(v: Vec<&'a str>) -> List {
+        List(v.into_iter().map(|s| s.to_string()).collect())
+    }
+}
+
+impl From<Vec<String>> for List {
+    fn from(v: Vec<String>) -> List {
+        List(v)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::List;
@@ -58,4 +86,11 @@ mod tests {
         l.append("Hello");
         assert!(l.get(0).unwrap() == "Hello");
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn extending_a_list() {
+        let mut l = List::from(vec!["a", "b"]);
+        l.extend(&List::from(vec!["c"]));
+        assert_eq!(l, List::from(vec!["a", "b", "c"]));
+    }
+}
@@ -0,0 +1,61 @@
+extern crate jam;
+
+use std::env;
+use std::io;
+use std::process;
+use jam::env::Environment;
+use jam::graph::Targets;
+use jam::jambase;
+use jam::lang::Interpreter;
+use jam::list::List;
+use jam::make;
+use jam::options::Options;
+use jam::variable::Assign;
+
+fn main() {
+    let options = match Options::parse(env::args().skip(1)) {
+        Ok(options) => options,
+        Err(e) => {
+            eprintln!("jam: {}", e);
+            process::exit(1);
+        }
+    };
+
+    let mut vars = Environment::new();
+    vars.import(env::vars());
+    let os = match env::consts::OS {
+        "macos" => "MACOSX".to_string(),
+        os => os.to_uppercase(),
+    };
+    let mut builtins = vec![("JAMVERSION", "2.5".to_string()), ("OS", os),
+                            ("OSPLAT", env::consts::ARCH.to_uppercase())];
+    if cfg!(unix) {
+        builtins.push(("UNIX", "true".to_string()));
+    } else if cfg!(windows) {
+        builtins.push(("NT", "true".to_string()));
+    }
+    for (name, value) in builtins {
+        vars.set(name, Assign::Set, &List::from(vec![value]));
+    }
+
+    make::start(&options);
+    let mut targets = Targets::new();
+    let mut jam = Interpreter::new();
+    let result = jam.include(&mut targets, &mut vars, "Jambase", jambase::data())
+        .and_then(|_| jam.scan_headers(&mut targets, &mut vars, &make::roots(&options)));
+    jam.trace();
+    if let Err(e) = result {
+        eprintln!("jam: {}", e);
+        process::exit(1);
+    }
+
+    let stdout = io::stdout();
+    match make::make(&mut targets, &vars, &options, &mut stdout.lock()) {
+        Ok(ref stats) if stats.failed + stats.cantfind + stats.cantmake == 0 && !stats.interrupted => {}
+        Ok(_) => process::exit(1),
+        Err(e) => {
+            eprintln!("jam: {}", e);
+            process::exit(1);
+        }
+    }
+}
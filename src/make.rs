@@ -0,0 +1,639 @@
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use cache::{CacheStore, HttpCache, LocalCache};
+use compdb;
+use debug;
+use env::Environment;
+use execute::{Executor, Stats};
+use export;
+use graph::Targets;
+use interrupt;
+use jobserver::Jobserver;
+use jam_depgraph::{Edge, NodeId};
+use list::List;
+use options::Options;
+use profile;
+use report;
+use schedule::CriticalPath;
+use script::ScriptWriter;
+use state::{self, State};
+use target::{Fate, Flags};
+use why;
+
+/// Turns on the debug output and the `--profile` timeline `options` ask
+/// for, before the Jambase is read, so that they cover parsing and
+/// evaluating the Jamfiles too.
+pub fn start(options: &Options) {
+    debug::install(options.debug);
+    if options.profile.is_some() {
+        profile::start();
+    }
+}
+
+/// The targets named in `options`, or `all` if there are none.
+pub fn roots(options: &Options) -> List {
+    if options.targets.is_empty() {
+        List::from(vec!["all"])
+    } else {
+        options.targets.clone()
+    }
+}
+
+/// Binds and updates the targets named in `options`, or `all` if there
+/// are none.  The timeline, if `start` did not begin it already, begins
+/// here.
+pub fn make(targets: &mut Targets, env: &Environment, options: &Options,
+            out: &mut dyn Write) -> io::Result<Stats> {
+    debug::install(options.debug);
+    if options.profile.is_some() && !profile::enabled() {
+        profile::start();
+    }
+    let result = make_roots(targets, env, options, out);
+    if let (Some(path), Some(profile)) = (options.profile.as_ref(), profile::finish()) {
+        profile.write(&mut File::create(path)?)?;
+    }
+    result
+}
+
+fn make_roots(targets: &mut Targets, env: &Environment, options: &Options,
+              out: &mut dyn Write) -> io::Result<Stats> {
+    let roots = roots(options);
+    let mut state = match options.content_hash || options.track_commands {
+        true => Some(State::load(options.state.as_deref().unwrap_or(state::DEFAULT_PATH))?),
+        false => None,
+    };
+    for name in &options.touched {
+        let id = targets.bind(name);
+        targets.get_mut(id).flags.push(Flags::Touched);
+    }
+    profile::span("make", "bind", || {
+        bind_with_state(targets, env, &roots, state.as_ref(), options.anyhow)
+    });
+
+    if let Some(ref path) = options.compile_commands {
+        compdb::write(targets, env, &roots, &mut File::create(path)?)?;
+        return Ok(Stats::default());
+    }
+    if let Some(format) = options.graph {
+        export::write(targets, &options.targets, format, out)?;
+        return Ok(Stats::default());
+    }
+    if options.why {
+        for name in roots.iter() {
+            why::explain(targets, name, out)?;
+        }
+        return Ok(Stats::default());
+    }
+
+    let cache: Option<Box<dyn CacheStore>> = match (options.remote_cache.as_ref(), options.cache.as_ref()) {
+        (Some(url), _) => Some(Box::new(HttpCache::new(url)
+            .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?)),
+        (_, Some(dir)) => Some(Box::new(LocalCache::new(dir))),
+        _ => None,
+    };
+    // Without a jobserver jam runs on the slot it was given anyway.
+    let jobserver = Jobserver::from_env().unwrap_or_else(|e| {
+        trace!(debug::MAKE, "warning: jobserver unavailable: {}", e);
+        None
+    });
+    // Opened before the executor, which borrows it, is made.
+    let mut cmdout = options.cmdout.as_ref().map(File::create).transpose()?;
+    let mut executor = Executor::new(targets, env, options, out);
+    if let Some(ref jobserver) = jobserver {
+        executor = executor.jobserver(jobserver);
+    }
+    if let Some(ref mut state) = state {
+        executor = executor.state(state);
+    }
+    if let Some(ref cache) = cache {
+        executor = executor.cache(&**cache);
+    }
+    if options.critical_path {
+        // The report about to be replaced times the previous run.
+        let previous = options.report.as_ref().and_then(|path| fs::read_to_string(path).ok());
+        let times = previous.map(|r| report::read_timings(&r)).unwrap_or_default();
+        executor = executor.priority(CriticalPath::new(times));
+    }
+    let stats = match cmdout {
+        Some(ref mut file) => {
+            let script = ScriptWriter::new(file)?;
+            executor.script(script).run(&roots)?
+        }
+        None => {
+            if !options.noexec {
+                interrupt::install();
+            }
+            profile::span("make", "update", || executor.run(&roots))?
+        }
+    };
+    if let Some(state) = state {
+        state.save()?;
+    }
+    if let Some(ref path) = options.report {
+        report::write(&stats, &mut File::create(path)?)?;
+    }
+    Ok(stats)
+}
+
+/// Binds every target reachable from `roots` to a file, reads its
+/// timestamp and decides its fate, as jam's `make0` does.
+pub fn bind(targets: &mut Targets, env: &Environment, roots: &List) {
+    bind_with_state(targets, env, roots, None, false);
+}
+
+/// Like `bind`, but with a `state` a target that has been built before is
+/// also out of date when its actions expand differently from the recorded
+/// ones, and if its inputs were recorded, exactly when they differ.
+/// With `anyhow`, as for `-a`, every target with actions is updated.
+pub fn bind_with_state(targets: &mut Targets, env: &Environment, roots: &List, state: Option<&State>,
+                       anyhow: bool) {
+    for name in roots.iter() {
+        let id = targets.bind(name);
+        make0(targets, env, state, anyhow, None, id, 0);
+    }
+}
+
+/// The targets `id` waits for: its own dependencies plus the headers
+/// those dependencies include, and the headers those include in turn.
+pub fn dependencies(targets: &Targets, id: NodeId) -> Vec<NodeId> {
+    dependency_edges(targets, id).into_iter().map(|(d, _)| d).collect()
+}
+
+/// Like `dependencies`, but pairs each with the target whose `INCLUDES`
+/// brought it in, if any.
+pub fn dependency_edges(targets: &Targets, id: NodeId) -> Vec<(NodeId, Option<NodeId>)> {
+    let graph = targets.graph();
+    let mut deps: Vec<(NodeId, Option<NodeId>)> = Vec::new();
+    for &d in graph.edges(id, Edge::Depends) {
+        if deps.iter().any(|&(x, _)| x == d) {
+            continue;
+        }
+        let start = deps.len();
+        deps.push((d, None));
+        // Every dependency added since `start` is scanned in turn, so
+        // includes of includes are followed, each header only once.
+        let mut next = start;
+        while next < deps.len() {
+            let via = deps[next].0;
+            for &i in graph.edges(via, Edge::Includes) {
+                if i != id && !deps.iter().any(|&(x, _)| x == i) {
+                    deps.push((i, Some(via)));
+                }
+            }
+            next += 1;
+        }
+    }
+    deps
+}
+
+/// Strips the `<grist>` from a target name.
+pub fn ungrist(name: &str) -> &str {
+    if name.starts_with('<') {
+        if let Some(i) = name.find('>') {
+            return &name[i + 1..];
+        }
+    }
+    name
+}
+
+/// Binds target `id`, reached from `parent`, and everything it depends on.
+fn make0(targets: &mut Targets, env: &Environment, state: Option<&State>, anyhow: bool,
+         parent: Option<NodeId>, id: NodeId, depth: usize) {
+    if targets.get(id).fate != Fate::Init {
+        return;
+    }
+    targets.get_mut(id).fate = Fate::Making;
+    let indent = " ".repeat(depth);
+
+    if !targets.get(id).has(Flags::NotFile) {
+        let bound = bind_name(targets, env, id);
+        let time = fs::metadata(&bound).and_then(|m| m.modified()).ok();
+        let t = targets.get_mut(id);
+        if bound != t.name() {
+            trace!(debug::MAKEPROG, "bind\t--\t{}{}: {}", indent, t.name(), bound);
+        }
+        match time.and_then(|t| t.duration_since(UNIX_EPOCH).ok()) {
+            Some(d) => trace!(debug::MAKEPROG, "time\t--\t{}{}: {}", indent, t.name(), d.as_secs()),
+            None => trace!(debug::MAKEPROG, "time\t--\t{}{}: missing", indent, t.name()),
+        }
+        t.bound = Some(bound);
+        t.time = time;
+    }
+
+    // A missing temporary is as new as the target it was used to build,
+    // so it need not be rebuilt unless its own sources have changed.
+    let parent_time = parent.and_then(|p| targets.get(p).time);
+    let t = targets.get_mut(id);
+    let borrowed = t.time.is_none() && t.has(Flags::Temporary) && parent_time.is_some();
+    if borrowed {
+        t.time = parent_time;
+        trace!(debug::MAKEPROG, "time\t--\t{}{}: parent's", indent, t.name());
+    }
+
+    let deps = dependencies(targets, id);
+    for &d in &deps {
+        make0(targets, env, state, anyhow, Some(id), d, depth + 1);
+    }
+
+    let mut fate = decide(targets, id, &deps);
+    if borrowed && fate == Fate::Stable {
+        fate = Fate::IsTmp;
+    }
+    let t = targets.get(id);
+    if anyhow && fate < Fate::Touched && !t.actions.is_empty() && !t.has(Flags::NoUpdate) {
+        fate = Fate::Touched;
+    }
+    if fate.needs_build() {
+        // Its missing temporaries are needed after all.
+        for &d in &deps {
+            if targets.get(d).fate == Fate::IsTmp {
+                targets.get_mut(d).fate = Fate::NeedTmp;
+                trace!(debug::MAKEPROG, "made+\t{}\t{} {}", Fate::NeedTmp, indent, targets.get(d).name());
+            }
+        }
+    }
+    if let Some(state) = state {
+        // The other targets of its actions appear in the command text.
+        let others: Vec<String> = targets.get(id).actions.iter()
+            .flat_map(|inv| inv.targets.iter().cloned().collect::<Vec<_>>())
+            .collect();
+        for name in others {
+            let other = targets.bind(&name);
+            make0(targets, env, Some(state), anyhow, None, other, depth + 1);
+        }
+        fate = recorded_fate(targets, env, id, fate, state);
+    }
+    targets.get_mut(id).fate = fate;
+
+    if debug::enabled(debug::DEPENDS) && !deps.is_empty() {
+        let names: Vec<&str> = deps.iter().map(|&d| targets.get(d).name()).collect();
+        trace!(debug::DEPENDS, "Depends {} : {} ;", targets.get(id).name(), names.join(" "));
+    }
+    let t = targets.get(id);
+    if fate.needs_build() && fate <= Fate::Outdated {
+        trace!(debug::CAUSES, "{} {}", fate, t.name());
+    }
+    let flag = if fate.needs_build() { "+" } else { "" };
+    trace!(debug::MAKEPROG, "made{}\t{}\t{}{}", flag, fate, indent, t.name());
+}
+
+/// The file target `id` names: in `LOCATE` if that is set on it, otherwise
+/// the first of the `SEARCH` directories it exists in, otherwise its name.
+pub fn bind_name(targets: &Targets, env: &Environment, id: NodeId) -> String {
+    let t = targets.get(id);
+    let name = ungrist(t.name());
+    let scope = t.scope(env);
+    let first = |var: &str| scope.get(var).and_then(|v| v.value().get(0).map(|s| s.to_string()));
+
+    if let Some(dir) = first("LOCATE") {
+        let p = Path::new(&dir).join(name).to_string_lossy().into_owned();
+        trace!(debug::SEARCH, "locate {}: {}", t.name(), p);
+        return p;
+    }
+    if let Some(search) = scope.get("SEARCH") {
+        for dir in search.value().iter() {
+            let p = Path::new(dir).join(name);
+            trace!(debug::SEARCH, "search {}: {}", t.name(), p.display());
+            if p.exists() {
+                return p.to_string_lossy().into_owned();
+            }
+        }
+    }
+    name.to_string()
+}
+
+fn decide(targets: &Targets, id: NodeId, deps: &[NodeId]) -> Fate {
+    let t = targets.get(id);
+    let mut newest: Option<SystemTime> = None;
+    let mut dep_fate = Fate::Stable;
+    for &d in deps {
+        let dep = targets.get(d);
+        if dep.fate > dep_fate && dep.fate != Fate::Making {
+            dep_fate = dep.fate;
+        }
+        if dep.time > newest {
+            newest = dep.time;
+        }
+    }
+
+    let mut fate = if t.has(Flags::NotFile) {
+        if t.has(Flags::Always) || t.has(Flags::Touched) { Fate::Touched } else { Fate::Stable }
+    } else {
+        match t.time {
+            None if !t.actions.is_empty() => Fate::Missing,
+            None if t.has(Flags::NoCare) => Fate::Stable,
+            None => Fate::CantFind,
+            Some(_) if t.has(Flags::Always) || t.has(Flags::Touched) => Fate::Touched,
+            Some(_) if t.has(Flags::NoUpdate) => Fate::Stable,
+            Some(time) if newest.is_some_and(|n| n > time) => Fate::Outdated,
+            Some(_) => Fate::Stable,
+        }
+    };
+
+    if fate < Fate::Touched && dep_fate.needs_build() && !t.has(Flags::NoUpdate) {
+        fate = Fate::Update;
+    }
+    if dep_fate >= Fate::CantFind && fate < Fate::CantFind && !t.has(Flags::NoCare) {
+        fate = Fate::CantMake;
+    }
+    fate
+}
+
+/// Overrides a timestamp fate with what was recorded about the target,
+/// if anything.  Only targets that exist and whose dependencies need no
+/// building are affected.
+fn recorded_fate(targets: &Targets, env: &Environment, id: NodeId, fate: Fate, state: &State) -> Fate {
+    let t = targets.get(id);
+    if (fate != Fate::Stable && fate != Fate::Outdated) || t.actions.is_empty() || t.has(Flags::NoUpdate) {
+        return fate;
+    }
+    let entry = match state.get(t.name()) {
+        Some(e) => e,
+        None => return fate,
+    };
+    if entry.command.is_some_and(|c| c != state::commands(targets, env, id)) {
+        trace!(debug::CAUSES, "command changed {}", t.name());
+        return Fate::Outdated;
+    }
+    match entry.inputs {
+        Some(recorded) if recorded == state::inputs(targets, id) => Fate::Stable,
+        Some(_) => Fate::Outdated,
+        None => fate,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+    use action::Action;
+    use std::rc::Rc;
+    use target::Flags;
+
+    fn with_action(ts: &mut Targets, name: &str) {
+        let touch = Rc::new(Action::new("Touch", vec![], "touch $(<)"));
+        ts.invoke(&touch, &List::from(vec![name]), &List::new());
+    }
+
+    #[test]
+    fn missing_targets_propagate_updates() {
+        let dir = TempDir::new("missing-test");
+        let main = dir.write("main.c", "").to_string_lossy().into_owned();
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["all"]), &List::from(vec!["prog"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec![main.as_str()]), Edge::Depends);
+        let all = ts.bind("all");
+        ts.get_mut(all).flags.push(Flags::NotFile);
+        with_action(&mut ts, "prog");
+
+        bind(&mut ts, &env, &List::from(vec!["all"]));
+        assert_eq!(ts.get(ts.find(&main).unwrap()).fate, Fate::Stable);
+        assert_eq!(ts.get(ts.find("prog").unwrap()).fate, Fate::Missing);
+        assert_eq!(ts.get(all).fate, Fate::Update);
+    }
+
+    #[test]
+    fn touched_targets_and_anyhow_override_timestamps() {
+        let dir = TempDir::new("touch-test");
+        for &(name, age) in &[("main.c", 100), ("main.o", 50), ("prog", 10)] {
+            let f = File::create(dir.join(name)).unwrap();
+            f.set_modified(SystemTime::now() - ::std::time::Duration::from_secs(age)).unwrap();
+        }
+        let mut env = Environment::new();
+        env.set("SEARCH", ::variable::Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        let fates = |touched: Option<&str>, anyhow: bool| {
+            let mut ts = Targets::new();
+            ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["main.o"]), Edge::Depends);
+            ts.add_edges(&List::from(vec!["main.o"]), &List::from(vec!["main.c"]), Edge::Depends);
+            with_action(&mut ts, "prog");
+            with_action(&mut ts, "main.o");
+            if let Some(name) = touched {
+                let id = ts.bind(name);
+                ts.get_mut(id).flags.push(Flags::Touched);
+            }
+            bind_with_state(&mut ts, &env, &List::from(vec!["prog"]), None, anyhow);
+            let fate = |name: &str| ts.get(ts.find(name).unwrap()).fate;
+            (fate("prog"), fate("main.o"), fate("main.c"))
+        };
+
+        assert_eq!(fates(None, false), (Fate::Stable, Fate::Stable, Fate::Stable));
+        assert_eq!(fates(Some("main.c"), false), (Fate::Update, Fate::Update, Fate::Touched));
+        assert_eq!(fates(Some("prog"), false), (Fate::Touched, Fate::Stable, Fate::Stable));
+        assert_eq!(fates(None, true), (Fate::Update, Fate::Touched, Fate::Stable));
+    }
+
+    #[test]
+    fn missing_temporaries_take_their_parents_time() {
+        let dir = TempDir::new("temporary-test");
+        let file = |name: &str, age: u64| {
+            let f = File::create(dir.join(name)).unwrap();
+            f.set_modified(SystemTime::now() - ::std::time::Duration::from_secs(age)).unwrap();
+        };
+        file("lib.a", 10);
+        file("gen.c", 100);
+        file("other.o", 5);
+        let mut env = Environment::new();
+        env.set("SEARCH", ::variable::Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        let fates = |deps: Vec<&str>| {
+            let mut ts = Targets::new();
+            ts.add_edges(&List::from(vec!["lib.a"]), &List::from(deps), Edge::Depends);
+            ts.add_edges(&List::from(vec!["gen.o"]), &List::from(vec!["gen.c"]), Edge::Depends);
+            with_action(&mut ts, "lib.a");
+            with_action(&mut ts, "gen.o");
+            let gen = ts.bind("gen.o");
+            ts.get_mut(gen).flags.push(Flags::Temporary);
+            bind(&mut ts, &env, &List::from(vec!["lib.a"]));
+            (ts.get(ts.find("lib.a").unwrap()).fate, ts.get(gen).fate)
+        };
+
+        assert_eq!(fates(vec!["gen.o"]), (Fate::Stable, Fate::IsTmp));
+        let rebuilt = fates(vec!["gen.o", "other.o"]);
+        file("gen.c", 1);
+        let changed = fates(vec!["gen.o"]);
+        assert_eq!(rebuilt, (Fate::Outdated, Fate::NeedTmp));
+        assert_eq!(changed, (Fate::Update, Fate::Outdated));
+    }
+
+    #[test]
+    fn includes_are_followed_through_headers() {
+        let dir = TempDir::new("includes-test");
+        for &(name, age) in &[("main.c", 300), ("main.h", 300), ("main.o", 200), ("util.h", 100)] {
+            let f = File::create(dir.join(name)).unwrap();
+            f.set_modified(SystemTime::now() - ::std::time::Duration::from_secs(age)).unwrap();
+        }
+        let mut env = Environment::new();
+        env.set("SEARCH", ::variable::Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["main.o"]), &List::from(vec!["main.c"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["main.c"]), &List::from(vec!["main.h"]), Edge::Includes);
+        ts.add_edges(&List::from(vec!["main.h"]), &List::from(vec!["util.h", "main.h"]), Edge::Includes);
+        ts.add_edges(&List::from(vec!["util.h"]), &List::from(vec!["main.h"]), Edge::Includes);
+        with_action(&mut ts, "main.o");
+
+        bind(&mut ts, &env, &List::from(vec!["main.o"]));
+        let main = ts.find("main.o").unwrap();
+        let names: Vec<(&str, Option<&str>)> = dependency_edges(&ts, main).into_iter()
+            .map(|(d, via)| (ts.get(d).name(), via.map(|v| ts.get(v).name())))
+            .collect();
+        assert_eq!(names, vec![("main.c", None), ("main.h", Some("main.c")), ("util.h", Some("main.h"))]);
+        assert_eq!(ts.get(main).fate, Fate::Outdated);
+    }
+
+    #[test]
+    fn unknown_sources_cannot_be_made() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["no-such-file.c"]), Edge::Depends);
+        with_action(&mut ts, "prog");
+
+        bind(&mut ts, &env, &List::from(vec!["prog"]));
+        assert_eq!(ts.get(ts.find("no-such-file.c").unwrap()).fate, Fate::CantFind);
+        assert_eq!(ts.get(ts.find("prog").unwrap()).fate, Fate::CantMake);
+    }
+
+    #[test]
+    fn commands_written_to_a_script() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        with_action(&mut ts, "no-such-stamp");
+        let dir = TempDir::new("make-script-test");
+        let path = dir.join("script.sh");
+        let options = Options {
+            cmdout: Some(path.to_string_lossy().into_owned()),
+            targets: List::from(vec!["no-such-stamp"]),
+            ..Options::default()
+        };
+
+        let mut out = Vec::new();
+        let stats = make(&mut ts, &env, &options, &mut out).unwrap();
+        let script = fs::read_to_string(&path).unwrap();
+        assert_eq!(stats.updated, 1);
+        assert_eq!(out, b"...updated 1 target(s)...\n");
+        assert!(script.ends_with("echo 'Touch no-such-stamp'\n/bin/sh -c 'touch no-such-stamp'\n"));
+    }
+
+    #[test]
+    fn profiles_written_for_the_run() {
+        let env = Environment::new();
+        let mut ts = Targets::new();
+        with_action(&mut ts, "no-such-stamp");
+        let dir = TempDir::new("make-profile-test");
+        let path = dir.join("profile.json");
+        let mut options = Options::parse(vec!["-n", "--profile", path.to_str().unwrap(), "no-such-stamp"]).unwrap();
+        options.debug = debug::Levels::none();
+
+        make(&mut ts, &env, &options, &mut Vec::new()).unwrap();
+        let trace = fs::read_to_string(&path).unwrap();
+        let names: Vec<&str> = trace.lines().filter(|l| l.contains("\"ph\""))
+            .filter_map(|l| l.split('"').nth(3)).collect();
+        assert_eq!(names, vec!["bind", "no-such-stamp", "update"]);
+        assert!(!profile::enabled());
+    }
+
+    #[test]
+    fn binding_progress_is_traced() {
+        let mut levels = debug::Levels::none();
+        levels.apply("+3").unwrap();
+        levels.apply("c").unwrap();
+        let out = debug::capture(levels, || {
+            let env = Environment::new();
+            let mut ts = Targets::new();
+            with_action(&mut ts, "no-such-stamp");
+            bind(&mut ts, &env, &List::from(vec!["no-such-stamp"]));
+        });
+        assert_eq!(out, "time\t--\tno-such-stamp: missing\n\
+                         missing no-such-stamp\n\
+                         made+\tmissing\tno-such-stamp\n");
+    }
+
+    #[test]
+    fn content_hash_overrides_timestamps() {
+        let dir = TempDir::new("content-hash-test");
+        fs::write(dir.join("in.c"), "int x;").unwrap();
+        let obj = File::create(dir.join("in.o")).unwrap();
+        obj.set_modified(SystemTime::now() - ::std::time::Duration::from_secs(100)).unwrap();
+
+        let mut env = Environment::new();
+        env.set("SEARCH", ::variable::Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        let bound = |state: &State| {
+            let mut ts = Targets::new();
+            ts.add_edges(&List::from(vec!["in.o"]), &List::from(vec!["in.c"]), Edge::Depends);
+            with_action(&mut ts, "in.o");
+            bind_with_state(&mut ts, &env, &List::from(vec!["in.o"]), Some(state), false);
+            ts
+        };
+
+        let mut state = State::load(dir.join("state")).unwrap();
+        let ts = bound(&state);
+        let id = ts.find("in.o").unwrap();
+        assert_eq!(ts.get(id).fate, Fate::Outdated);
+        state.entry("in.o").inputs = Some(state::inputs(&ts, id));
+        let ts = bound(&state);
+        assert_eq!(ts.get(ts.find("in.o").unwrap()).fate, Fate::Stable);
+        fs::write(dir.join("in.c"), "int y;").unwrap();
+        let ts = bound(&state);
+        assert_eq!(ts.get(ts.find("in.o").unwrap()).fate, Fate::Outdated);
+    }
+
+    #[test]
+    fn changed_commands_are_outdated() {
+        let dir = TempDir::new("command-test");
+        let out = dir.write("prog", "").to_string_lossy().into_owned();
+        let mut env = Environment::new();
+        env.set("FLAGS", ::variable::Assign::Set, &List::from(vec!["-O"]));
+        let bound = |env: &Environment, state: &State| {
+            let mut ts = Targets::new();
+            let cc = Rc::new(Action::new("Cc", vec![], "cc $(FLAGS) -o $(<)"));
+            ts.invoke(&cc, &List::from(vec![out.as_str()]), &List::new());
+            bind_with_state(&mut ts, env, &List::from(vec![out.as_str()]), Some(state), false);
+            ts
+        };
+
+        let mut state = State::load(dir.join("state")).unwrap();
+        let ts = bound(&env, &state);
+        let id = ts.find(&out).unwrap();
+        assert_eq!(ts.get(id).fate, Fate::Stable);
+        state.entry(&out).command = Some(state::commands(&ts, &env, id));
+        assert_eq!(bound(&env, &state).get(id).fate, Fate::Stable);
+        env.set("FLAGS", ::variable::Assign::Set, &List::from(vec!["-g"]));
+        assert_eq!(bound(&env, &state).get(id).fate, Fate::Outdated);
+    }
+
+    #[test]
+    fn targets_restored_from_cache() {
+        let dir = TempDir::new("make-cache-test");
+        let out = dir.name("out");
+        let runs = dir.join("runs");
+        let options = Options {
+            cache: Some(dir.name("cache")),
+            targets: List::from(vec![out.as_str()]),
+            ..Options::default()
+        };
+        let build = || {
+            let mut ts = Targets::new();
+            let command = format!("echo run >> {}; echo built > $(<)", runs.display());
+            let action = Rc::new(Action::new("Build", vec![], &command));
+            ts.invoke(&action, &List::from(vec![out.as_str()]), &List::new());
+            let mut log = Vec::new();
+            let stats = make(&mut ts, &Environment::new(), &options, &mut log).unwrap();
+            (stats.updated, String::from_utf8(log).unwrap())
+        };
+
+        assert_eq!(build(), (1, format!("Build {}\n...updated 1 target(s)...\n", out)));
+        fs::remove_file(&out).unwrap();
+        assert_eq!(build(), (1, format!("...restored {} from cache...\n...updated 1 target(s)...\n", out)));
+        let built = fs::read_to_string(&out).unwrap();
+        let runs = fs::read_to_string(&runs).unwrap();
+        assert_eq!(built, "built\n");
+        assert_eq!(runs, "run\n");
+    }
+
+    #[test]
+    fn grist_is_stripped_when_binding() {
+        assert_eq!(ungrist("<src>main.c"), "main.c");
+        assert_eq!(ungrist("main.c"), "main.c");
+    }
+}
@@ -0,0 +1,297 @@
+use debug::{self, Levels};
+use export;
+use list::List;
+
+/// The command line settings that control a jam run.
+#[derive(Debug, Default, PartialEq)]
+pub struct Options {
+    /// `-n`: print the commands that would be run without running them.
+    pub noexec: bool,
+    /// `-a`: update every target, whether it is out of date or not.
+    pub anyhow: bool,
+    /// `-t target`: update `target`, and so everything that depends on it,
+    /// as if it had just changed.
+    pub touched: Vec<String>,
+    /// `-g`: update the targets with the newest sources first.
+    pub newest_first: bool,
+    /// `--critical-path`: update the targets at the head of the longest
+    /// chains of updates first, timed by the previous run's `--report`.
+    pub critical_path: bool,
+    /// `-j n`: run up to `n` commands at once.
+    pub jobs: Option<usize>,
+    /// `-q`: stop at the first failure instead of carrying on with the
+    /// targets that do not depend on it.
+    pub quit: bool,
+    /// `--keep-going=n`: stop after `n` failures; 0 never stops.
+    pub keep_going: Option<usize>,
+    /// `-o file`: write the updating actions to a shell script.
+    pub cmdout: Option<String>,
+    /// `-d`: the debug output levels.
+    pub debug: Levels,
+    /// `--graph=dot|json`: write the dependency graph instead of updating.
+    pub graph: Option<export::Format>,
+    /// `--compile-commands=file`: write a compilation database instead of
+    /// updating.
+    pub compile_commands: Option<String>,
+    /// `jam why target ...`: explain why the targets are out of date
+    /// instead of updating them.
+    pub why: bool,
+    /// `--content-hash`: decide whether targets are up to date from the
+    /// contents of their inputs rather than their timestamps.
+    pub content_hash: bool,
+    /// `--track-commands`: update targets whose actions expand differently
+    /// from when they were last updated.  Implied by `--content-hash`.
+    pub track_commands: bool,
+    /// `--profile=file`: write a timeline of the run in Chrome's
+    /// trace-event format.
+    pub profile: Option<String>,
+    /// `--report=file`: write a JSON report of the run's counts, slowest
+    /// commands, failures and the time each command took.
+    pub report: Option<String>,
+    /// `--keep-failed`: leave the targets of failed commands in place for
+    /// debugging instead of removing them.
+    pub keep_failed: bool,
+    /// `--log-dir=dir`: keep the commands run for each target and their
+    /// output in `dir/target.log`.
+    pub log_dir: Option<String>,
+    /// `--cache=dir`: restore targets from, and save them to, a build cache
+    /// kept in a local directory.
+    pub cache: Option<String>,
+    /// `--remote-cache=url`: use a build cache on an HTTP server instead,
+    /// such as bazel-remote run with `--disable_http_ac_validation`.
+    pub remote_cache: Option<String>,
+    /// `--state=file`: where to keep the records these two options need.
+    pub state: Option<String>,
+    /// The targets named on the command line.
+    pub targets: List,
+}
+
+/// The long options that take a value, which may be given either as
+/// `--name=value` or as `--name value`.
+const VALUED: &[&str] = &["cache", "compile-commands", "graph", "log-dir", "profile",
+                          "remote-cache", "report", "state"];
+
+impl Options {
+    /// Parses jam's command line arguments, not including the program
+    /// name.  Options come first and everything after them is a target.
+    pub fn parse<I, S>(args: I) -> Result<Options, String>
+        where I: IntoIterator<Item = S>, S: AsRef<str>
+    {
+        let mut options = Options::default();
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
+            let arg = arg.as_ref();
+            if let Some(long) = arg.strip_prefix("--") {
+                if VALUED.contains(&long) {
+                    let v = args.next().ok_or_else(|| format!("option --{} needs an argument", long))?;
+                    options.long(&format!("{}={}", long, v.as_ref()))?;
+                } else {
+                    options.long(long)?;
+                }
+                continue;
+            }
+            if !arg.starts_with('-') || arg.len() < 2 {
+                if arg == "why" && options.targets.is_empty() {
+                    options.why = true;
+                } else {
+                    options.targets.append(arg);
+                }
+                break;
+            }
+            for (i, flag) in arg[1..].char_indices() {
+                match flag {
+                    'd' => {
+                        let spec = value(&arg[i + 2..], &mut args, flag)?;
+                        options.debug.apply(&spec)?;
+                        break;
+                    }
+                    'n' => {
+                        options.noexec = true;
+                        options.debug.set(debug::MAKE);
+                        options.debug.set(debug::MAKEQ);
+                        options.debug.set(debug::EXEC);
+                    }
+                    'g' => options.newest_first = true,
+                    'q' => options.quit = true,
+                    'a' => options.anyhow = true,
+                    't' => {
+                        options.touched.push(value(&arg[i + 2..], &mut args, flag)?);
+                        break;
+                    }
+                    'j' => {
+                        let n = value(&arg[i + 2..], &mut args, flag)?;
+                        options.jobs = match n.parse() {
+                            Ok(0) | Err(_) => return Err(format!("bad job count -j{}", n)),
+                            Ok(n) => Some(n),
+                        };
+                        break;
+                    }
+                    'o' => {
+                        options.cmdout = Some(value(&arg[i + 2..], &mut args, flag)?);
+                        break;
+                    }
+                    _ => return Err(format!("unknown option -{}", flag)),
+                }
+            }
+        }
+        for arg in args {
+            options.targets.append(arg.as_ref());
+        }
+        Ok(options)
+    }
+
+    /// Handles a `--name=value` option.
+    fn long(&mut self, arg: &str) -> Result<(), String> {
+        let (name, value) = match arg.find('=') {
+            Some(i) => (&arg[..i], Some(&arg[i + 1..])),
+            None => (arg, None),
+        };
+        match (name, value) {
+            ("graph", Some(v)) => {
+                self.graph = Some(export::Format::parse(v)
+                    .ok_or_else(|| format!("unknown graph format {}", v))?);
+            }
+            ("compile-commands", Some(v)) => self.compile_commands = Some(v.to_string()),
+            ("content-hash", None) => self.content_hash = true,
+            ("track-commands", None) => self.track_commands = true,
+            ("keep-failed", None) => self.keep_failed = true,
+            ("critical-path", None) => self.critical_path = true,
+            ("keep-going", None) => self.keep_going = None,
+            ("keep-going", Some(v)) => {
+                let n = v.parse().map_err(|_| format!("bad failure count --keep-going={}", v))?;
+                self.keep_going = if n == 0 { None } else { Some(n) };
+            }
+            ("profile", Some(v)) => self.profile = Some(v.to_string()),
+            ("report", Some(v)) => self.report = Some(v.to_string()),
+            ("log-dir", Some(v)) => self.log_dir = Some(v.to_string()),
+            ("cache", Some(v)) => self.cache = Some(v.to_string()),
+            ("remote-cache", Some(v)) => self.remote_cache = Some(v.to_string()),
+            ("state", Some(v)) => self.state = Some(v.to_string()),
+            _ => return Err(format!("unknown option --{}", arg)),
+        }
+        Ok(())
+    }
+}
+
+impl Options {
+    /// How many failures to stop after, if any.
+    pub fn failure_limit(&self) -> Option<usize> {
+        if self.quit { Some(1) } else { self.keep_going }
+    }
+}
+
+/// The value of an option, either attached (`-ofile`) or the next argument.
+fn value<I, S>(attached: &str, args: &mut I, flag: char) -> Result<String, String>
+    where I: Iterator<Item = S>, S: AsRef<str>
+{
+    if !attached.is_empty() {
+        return Ok(attached.to_string());
+    }
+    args.next()
+        .map(|a| a.as_ref().to_string())
+        .ok_or_else(|| format!("option -{} needs an argument", flag))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_flags_and_targets() {
+        let o = Options::parse(vec!["-ng", "all", "-n"]).unwrap();
+        assert!(o.noexec && o.newest_first);
+        assert_eq!(o.targets, List::from(vec!["all", "-n"]));
+        assert!(!Options::parse(Vec::<String>::new()).unwrap().noexec);
+        assert!(Options::parse(vec!["-Z"]).is_err());
+    }
+
+    #[test]
+    fn parses_option_values() {
+        let o = Options::parse(vec!["-o", "build.sh", "all"]).unwrap();
+        assert_eq!(o.cmdout, Some("build.sh".to_string()));
+        assert_eq!(o.targets, List::from(vec!["all"]));
+        let o = Options::parse(vec!["-nobuild.sh"]).unwrap();
+        assert!(o.noexec);
+        assert_eq!(o.cmdout, Some("build.sh".to_string()));
+        assert!(Options::parse(vec!["-o"]).is_err());
+    }
+
+    #[test]
+    fn parses_long_options() {
+        let o = Options::parse(vec!["--graph=dot", "-n", "prog"]).unwrap();
+        assert_eq!(o.graph, Some(export::Format::Dot));
+        assert!(o.noexec);
+        assert!(Options::parse(vec!["--graph=svg"]).is_err());
+        assert!(Options::parse(vec!["--graph"]).is_err());
+        let o = Options::parse(vec!["--compile-commands=compile_commands.json"]).unwrap();
+        assert_eq!(o.compile_commands, Some("compile_commands.json".to_string()));
+        assert!(Options::parse(vec!["--frobnicate"]).is_err());
+        let o = Options::parse(vec!["--content-hash", "--state=build/.jamstate"]).unwrap();
+        assert!(o.content_hash);
+        assert_eq!(o.state, Some("build/.jamstate".to_string()));
+        assert!(Options::parse(vec!["--content-hash=yes"]).is_err());
+        assert!(Options::parse(vec!["--track-commands"]).unwrap().track_commands);
+        let o = Options::parse(vec!["--profile", "out.json", "all"]).unwrap();
+        assert_eq!(o.profile, Some("out.json".to_string()));
+        assert_eq!(o.targets, List::from(vec!["all"]));
+        assert_eq!(Options::parse(vec!["--report=jam.json"]).unwrap().report, Some("jam.json".to_string()));
+        assert!(Options::parse(vec!["--keep-failed"]).unwrap().keep_failed);
+        assert!(Options::parse(vec!["--critical-path", "-g"]).unwrap().critical_path);
+        assert_eq!(Options::parse(vec!["--log-dir=logs"]).unwrap().log_dir, Some("logs".to_string()));
+        assert_eq!(Options::parse(vec!["--cache=/var/cache/jam"]).unwrap().cache,
+                   Some("/var/cache/jam".to_string()));
+        assert_eq!(Options::parse(vec!["--remote-cache=http://cache:8080"]).unwrap().remote_cache,
+                   Some("http://cache:8080".to_string()));
+    }
+
+    #[test]
+    fn parses_rebuild_options() {
+        let o = Options::parse(vec!["-a", "-t", "main.c", "-tutil.h", "prog"]).unwrap();
+        assert!(o.anyhow);
+        assert_eq!(o.touched, vec!["main.c", "util.h"]);
+        assert_eq!(o.targets, List::from(vec!["prog"]));
+        assert!(Options::parse(vec!["-t"]).is_err());
+    }
+
+    #[test]
+    fn parses_failure_policies() {
+        assert_eq!(Options::parse(Vec::<String>::new()).unwrap().failure_limit(), None);
+        assert_eq!(Options::parse(vec!["-nq"]).unwrap().failure_limit(), Some(1));
+        assert_eq!(Options::parse(vec!["--keep-going=3"]).unwrap().failure_limit(), Some(3));
+        assert_eq!(Options::parse(vec!["--keep-going=0"]).unwrap().failure_limit(), None);
+        assert_eq!(Options::parse(vec!["--keep-going"]).unwrap().failure_limit(), None);
+        assert!(Options::parse(vec!["--keep-going=many"]).is_err());
+    }
+
+    #[test]
+    fn parses_why_queries() {
+        let o = Options::parse(vec!["-d3", "why", "prog"]).unwrap();
+        assert!(o.why);
+        assert_eq!(o.targets, List::from(vec!["prog"]));
+        let o = Options::parse(vec!["all", "why"]).unwrap();
+        assert!(!o.why);
+        assert_eq!(o.targets, List::from(vec!["all", "why"]));
+    }
+
+    #[test]
+    fn parses_job_counts() {
+        assert_eq!(Options::parse(Vec::<String>::new()).unwrap().jobs, None);
+        assert_eq!(Options::parse(vec!["-j", "4", "all"]).unwrap().jobs, Some(4));
+        let o = Options::parse(vec!["-nj8"]).unwrap();
+        assert!(o.noexec);
+        assert_eq!(o.jobs, Some(8));
+        assert!(Options::parse(vec!["-j0"]).is_err());
+        assert!(Options::parse(vec!["-jmany"]).is_err());
+        assert!(Options::parse(vec!["-j"]).is_err());
+    }
+
+    #[test]
+    fn parses_debug_levels() {
+        let o = Options::parse(vec!["-d", "+7", "-dx"]).unwrap();
+        assert!(o.debug.enabled(debug::VARSET) && o.debug.enabled(debug::EXEC));
+        assert!(!o.debug.enabled(debug::MAKE));
+        let o = Options::parse(vec!["-n"]).unwrap();
+        assert!(o.debug.enabled(debug::MAKEQ) && o.debug.enabled(debug::EXEC));
+        assert!(Options::parse(vec!["-dz"]).is_err());
+    }
+}
@@ -1,21 +1,117 @@
 use std::fmt;
-use std::path;
 
-#[derive(Debug, PartialEq)]
+/// A file name split into the parts that the `:G`, `:R`, `:D`, `:B`, `:S`
+/// and `:M` modifiers select and replace, as jam's `path_parse` does:
+/// `<grist>dir/base.suffix(member)`.
+#[derive(Clone, Debug, Default, PartialEq)]
 pub struct Path {
-    path: Box<path::Path>,
-    grist: Option<String>,
-    member: Option<String>
+    pub grist: String,
+    /// Put before a relative directory when the path is written out.
+    pub root: String,
+    pub dir: String,
+    pub base: String,
+    pub suffix: String,
+    /// The member of an archive, as in `libc.a(printf.o)`.
+    pub member: String,
+}
+
+impl Path {
+    pub fn parse(name: &str) -> Path {
+        let mut path = Path::default();
+        let mut file = name;
+        if file.starts_with('<') {
+            if let Some(i) = file.find('>') {
+                path.grist = file[..i + 1].to_string();
+                file = &file[i + 1..];
+            }
+        }
+        if let Some(i) = file.rfind('/') {
+            // The directory of `/x` is `/`, not nothing.
+            path.dir = file[..i.max(1)].to_string();
+            file = &file[i + 1..];
+        }
+        if let (Some(i), true) = (file.find('('), file.ends_with(')')) {
+            path.member = file[i + 1..file.len() - 1].to_string();
+            file = &file[..i];
+        }
+        if let Some(i) = file.rfind('.') {
+            path.suffix = file[i..].to_string();
+            file = &file[..i];
+        }
+        path.base = file.to_string();
+        path
+    }
+
+    /// Leaves only the directory, for `:P`.
+    pub fn parent(&mut self) {
+        self.base.clear();
+        self.suffix.clear();
+        self.member.clear();
+    }
+
+    /// The part modifier `flag` selects, if it is one of `GRDBSM`.
+    pub fn part_mut(&mut self, flag: char) -> Option<&mut String> {
+        match flag {
+            'G' => Some(&mut self.grist),
+            'R' => Some(&mut self.root),
+            'D' => Some(&mut self.dir),
+            'B' => Some(&mut self.base),
+            'S' => Some(&mut self.suffix),
+            'M' => Some(&mut self.member),
+            _ => None,
+        }
+    }
 }
 
 impl fmt::Display for Path {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        unimplemented!();    
+        if !self.grist.is_empty() {
+            let open = if self.grist.starts_with('<') { "" } else { "<" };
+            let close = if self.grist.ends_with('>') { "" } else { ">" };
+            write!(f, "{}{}{}", open, self.grist, close)?;
+        }
+        if !self.root.is_empty() && self.root != "." && !self.dir.starts_with('/') {
+            write!(f, "{}/", self.root)?;
+        }
+        f.write_str(&self.dir)?;
+        if !self.dir.is_empty() && self.dir != "/" && !(self.base.is_empty() && self.suffix.is_empty()) {
+            f.write_str("/")?;
+        }
+        write!(f, "{}{}", self.base, self.suffix)?;
+        if !self.member.is_empty() {
+            write!(f, "({})", self.member)?;
+        }
+        Ok(())
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use super::Path;
+
     #[test]
-    fn works() {}
-}
\ No newline at end of file
+    fn parse_and_build() {
+        let p = Path::parse("<src>sub/dir/file.tar.gz");
+        assert_eq!((p.grist.as_str(), p.dir.as_str(), p.base.as_str(), p.suffix.as_str()),
+                   ("<src>", "sub/dir", "file.tar", ".gz"));
+        assert_eq!(p.to_string(), "<src>sub/dir/file.tar.gz");
+
+        let p = Path::parse("/libc.a(printf.o)");
+        assert_eq!((p.dir.as_str(), p.base.as_str(), p.member.as_str()), ("/", "libc", "printf.o"));
+        assert_eq!(p.to_string(), "/libc.a(printf.o)");
+    }
+
+    #[test]
+    fn roots_and_parents() {
+        let mut p = Path::parse("obj/main.o");
+        p.root = "build".to_string();
+        p.grist = "g".to_string();
+        assert_eq!(p.to_string(), "<g>build/obj/main.o");
+        p.parent();
+        assert_eq!(p.to_string(), "<g>build/obj");
+
+        let mut p = Path::parse("/abs/main.o");
+        p.root = "build".to_string();
+        assert_eq!(p.to_string(), "/abs/main.o");
+    }
+}
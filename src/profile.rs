@@ -0,0 +1,125 @@
+//! A timeline of a run in the Chrome trace-event format, as written by
+//! `--profile` and read by `about:tracing` or Perfetto.
+//!
+//! Like the debug levels, the recorder is kept per thread: `start` turns it
+//! on, any part of jam can then add spans, and `finish` takes the result.
+
+use std::cell::RefCell;
+use std::io::{self, Write};
+use std::time::{Duration, Instant};
+use json;
+
+/// Something that took time: a phase of the run or a command.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Event {
+    pub category: &'static str,
+    pub name: String,
+    /// When it began, from the start of the profile.
+    pub start: Duration,
+    pub duration: Duration,
+    /// The job slot a command ran in; phases of jam itself use slot 0.
+    pub slot: usize,
+}
+
+#[derive(Debug)]
+pub struct Profile {
+    origin: Instant,
+    pub events: Vec<Event>,
+}
+
+thread_local! {
+    static PROFILE: RefCell<Option<Profile>> = const { RefCell::new(None) };
+}
+
+/// Starts recording on this thread.
+pub fn start() {
+    PROFILE.with(|p| *p.borrow_mut() = Some(Profile { origin: Instant::now(), events: Vec::new() }));
+}
+
+/// Stops recording and returns what was recorded, if anything was.
+pub fn finish() -> Option<Profile> {
+    PROFILE.with(|p| p.borrow_mut().take())
+}
+
+pub fn enabled() -> bool {
+    PROFILE.with(|p| p.borrow().is_some())
+}
+
+/// Records an event that ran from `start` until now.
+pub fn record(category: &'static str, name: &str, start: Instant, slot: usize) {
+    let end = Instant::now();
+    PROFILE.with(|p| {
+        if let Some(ref mut profile) = *p.borrow_mut() {
+            profile.events.push(Event {
+                category,
+                name: name.to_string(),
+                start: start.saturating_duration_since(profile.origin),
+                duration: end.saturating_duration_since(start),
+                slot,
+            });
+        }
+    });
+}
+
+/// Runs `f`, recording it as a phase of jam named `name`.
+pub fn span<T, F: FnOnce() -> T>(category: &'static str, name: &str, f: F) -> T {
+    let start = Instant::now();
+    let result = f();
+    record(category, name, start, 0);
+    result
+}
+
+impl Profile {
+    /// Writes the events as complete (`"ph": "X"`) trace events, with
+    /// each job slot as a thread of its own.
+    pub fn write(&self, out: &mut dyn Write) -> io::Result<()> {
+        writeln!(out, "{{\"traceEvents\": [")?;
+        for (n, e) in self.events.iter().enumerate() {
+            write!(out, "  {{\"name\": {}, \"cat\": {}, \"ph\": \"X\", \"ts\": {}, \"dur\": {}, \
+                         \"pid\": 1, \"tid\": {}}}",
+                   json::string(&e.name), json::string(e.category),
+                   e.start.as_micros(), e.duration.as_micros(), e.slot)?;
+            writeln!(out, "{}", if n + 1 < self.events.len() { "," } else { "" })?;
+        }
+        writeln!(out, "], \"displayTimeUnit\": \"ms\"}}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+
+    #[test]
+    fn recording_spans() {
+        span("make", "ignored", || ());
+        assert!(finish().is_none());
+
+        start();
+        assert_eq!(span("make", "bind", || 42), 42);
+        record("action", "Cc a.o", Instant::now(), 1);
+        let profile = finish().unwrap();
+        assert!(!enabled());
+        let names: Vec<(&str, usize)> = profile.events.iter().map(|e| (e.name.as_str(), e.slot)).collect();
+        assert_eq!(names, vec![("bind", 0), ("Cc a.o", 1)]);
+    }
+
+    #[test]
+    fn trace_event_output() {
+        let profile = Profile {
+            origin: Instant::now(),
+            events: vec![Event {
+                category: "action",
+                name: "Cc \"a\".o".to_string(),
+                start: Duration::from_micros(1500),
+                duration: Duration::from_millis(20),
+                slot: 1,
+            }],
+        };
+        let mut out = Vec::new();
+        profile.write(&mut out).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "{\"traceEvents\": [\n  \
+            {\"name\": \"Cc \\\"a\\\".o\", \"cat\": \"action\", \"ph\": \"X\", \"ts\": 1500, \"dur\": 20000, \
+            \"pid\": 1, \"tid\": 1}\n], \"displayTimeUnit\": \"ms\"}\n");
+    }
+}
@@ -0,0 +1,131 @@
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::io::{self, Write};
+use std::time::Duration;
+use execute::{Stats, Timing};
+use json;
+
+/// How many of the slowest commands a report lists.
+pub const SLOWEST: usize = 10;
+
+fn seconds(d: Duration) -> String {
+    format!("{:.3}", d.as_secs_f64())
+}
+
+fn timings<'a, I: Iterator<Item = &'a Timing>>(timings: I) -> String {
+    let entries: Vec<String> = timings
+        .map(|t| format!("\n    {{\"action\": {}, \"target\": {}, \"seconds\": {}}}",
+                         json::string(&t.action), json::string(&t.target), seconds(t.time)))
+        .collect();
+    if entries.is_empty() {
+        "[]".to_string()
+    } else {
+        format!("[{}\n  ]", entries.join(","))
+    }
+}
+
+/// Writes a JSON report of a run for `--report`: the counts jam prints at
+/// the end, the slowest commands, every command that failed and the time
+/// every command took.
+pub fn write(stats: &Stats, out: &mut dyn Write) -> io::Result<()> {
+    let mut slowest: Vec<&Timing> = stats.timings.iter().collect();
+    slowest.sort_by_key(|t| Reverse(t.time));
+    writeln!(out, "{{")?;
+    writeln!(out, "  \"updated\": {},", stats.updated)?;
+    writeln!(out, "  \"failed\": {},", stats.failed)?;
+    writeln!(out, "  \"skipped\": {},", stats.skipped)?;
+    writeln!(out, "  \"cantfind\": {},", stats.cantfind)?;
+    writeln!(out, "  \"cantmake\": {},", stats.cantmake)?;
+    writeln!(out, "  \"interrupted\": {},", stats.interrupted)?;
+    writeln!(out, "  \"seconds\": {},", seconds(stats.elapsed))?;
+    writeln!(out, "  \"slowest\": {},", timings(slowest.into_iter().take(SLOWEST)))?;
+    writeln!(out, "  \"failures\": {},", timings(stats.timings.iter().filter(|t| !t.ok)))?;
+    writeln!(out, "  \"timings\": {}", timings(stats.timings.iter()))?;
+    writeln!(out, "}}")
+}
+
+/// Reads back from a report `write` wrote how long updating each target
+/// took, adding up the times of its commands.  Anything else in the report
+/// is ignored, as is a report that cannot be read.
+pub fn read_timings(report: &str) -> HashMap<String, Duration> {
+    let mut times = HashMap::new();
+    let mut in_timings = false;
+    for line in report.lines().map(str::trim) {
+        if line.starts_with('"') {
+            in_timings = line.starts_with("\"timings\":");
+            continue;
+        }
+        if !in_timings {
+            continue;
+        }
+        let entry = line.strip_prefix("{\"action\": ")
+            .and_then(json::parse_string)
+            .and_then(|(_, rest)| rest.strip_prefix(", \"target\": "))
+            .and_then(json::parse_string)
+            .and_then(|(target, rest)| {
+                let secs = rest.strip_prefix(", \"seconds\": ")?.trim_end_matches(',').strip_suffix('}')?;
+                Some((target, Duration::try_from_secs_f64(secs.parse().ok()?).ok()?))
+            });
+        if let Some((target, time)) = entry {
+            *times.entry(target).or_insert(Duration::ZERO) += time;
+        }
+    }
+    times
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str;
+
+    fn timing(target: &str, millis: u64, ok: bool) -> Timing {
+        Timing {
+            action: "Cc".to_string(),
+            target: target.to_string(),
+            time: Duration::from_millis(millis),
+            ok,
+        }
+    }
+
+    #[test]
+    fn report_output() {
+        let mut stats = Stats { updated: 2, failed: 1, cantfind: 1, elapsed: Duration::from_millis(1500), ..Stats::default() };
+        stats.timings = vec![timing("a.o", 200, true), timing("b.o", 900, false), timing("c.o", 400, true)];
+        let mut out = Vec::new();
+        write(&stats, &mut out).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), r#"{
+  "updated": 2,
+  "failed": 1,
+  "skipped": 0,
+  "cantfind": 1,
+  "cantmake": 0,
+  "interrupted": false,
+  "seconds": 1.500,
+  "slowest": [
+    {"action": "Cc", "target": "b.o", "seconds": 0.900},
+    {"action": "Cc", "target": "c.o", "seconds": 0.400},
+    {"action": "Cc", "target": "a.o", "seconds": 0.200}
+  ],
+  "failures": [
+    {"action": "Cc", "target": "b.o", "seconds": 0.900}
+  ],
+  "timings": [
+    {"action": "Cc", "target": "a.o", "seconds": 0.200},
+    {"action": "Cc", "target": "b.o", "seconds": 0.900},
+    {"action": "Cc", "target": "c.o", "seconds": 0.400}
+  ]
+}
+"#);
+
+        let times = read_timings(str::from_utf8(&out).unwrap());
+        assert_eq!(times.len(), 3);
+        assert_eq!(times["b.o"], Duration::from_millis(900));
+        assert_eq!(times["a.o"], Duration::from_millis(200));
+
+        let mut out = Vec::new();
+        write(&Stats::default(), &mut out).unwrap();
+        assert!(str::from_utf8(&out).unwrap().contains("\"slowest\": [],\n  \"failures\": [],\n  \"timings\": []\n"));
+        assert!(read_timings(str::from_utf8(&out).unwrap()).is_empty());
+        assert!(read_timings("not a report").is_empty());
+    }
+}
@@ -0,0 +1,233 @@
+//! The order in which the executor walks the dependencies of a target,
+//! and in which it starts updating the targets that are ready to be under
+//! `-j`.
+//!
+//! By default they are taken in the order they were declared and reached.
+//! Other policies implement `Priority` to put the most urgent ones first:
+//! `-g` the ones with the newest sources, `--critical-path` the ones at
+//! the head of the longest chains of updates still to run.
+
+use std::cmp::Reverse;
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use graph::Targets;
+use jam_depgraph::NodeId;
+use make;
+
+/// A policy for ordering targets.
+pub trait Priority {
+    /// Reorders `deps`, the dependencies of one target, so that the most
+    /// urgent come first.  Those it cannot tell apart keep their order.
+    fn order(&mut self, targets: &Targets, deps: &mut [NodeId]);
+
+    /// Reorders `ready`, the targets whose dependencies are all made, so
+    /// that the one to update first comes first.  Those it cannot tell
+    /// apart keep their order.
+    fn order_ready(&mut self, targets: &Targets, ready: &mut [NodeId]) {
+        self.order(targets, ready);
+    }
+}
+
+/// Looks up the value `memo` keeps for target `id` in `policy`, working it
+/// out with `compute` the first time.  Until `compute` returns, `id` has
+/// the value `cycle`, so that a dependency cycle back to it ends there
+/// instead of recursing forever.
+fn memoized<P, T, F>(policy: &mut P, memo: fn(&mut P) -> &mut HashMap<NodeId, T>, id: NodeId, cycle: T,
+                     compute: F) -> T
+    where T: Copy, F: FnOnce(&mut P) -> T
+{
+    if let Some(&value) = memo(policy).get(&id) {
+        return value;
+    }
+    memo(policy).insert(id, cycle);
+    let value = compute(policy);
+    memo(policy).insert(id, value);
+    value
+}
+
+/// The order the dependencies were declared in.
+pub struct Declared;
+
+impl Priority for Declared {
+    fn order(&mut self, _: &Targets, _: &mut [NodeId]) {}
+}
+
+/// The targets with the newest sources first, for `-g`, so that what was
+/// just edited is rebuilt first.  A target's sources are the leaves of the
+/// dependency graph below it, with the timestamps binding read.
+#[derive(Default)]
+pub struct NewestFirst {
+    leaves: HashMap<NodeId, Option<SystemTime>>,
+}
+
+impl NewestFirst {
+    pub fn new() -> NewestFirst { NewestFirst::default() }
+
+    /// The time of the newest source of target `id`.
+    fn leaf(&mut self, targets: &Targets, id: NodeId) -> Option<SystemTime> {
+        memoized(self, |s| &mut s.leaves, id, None, |s| {
+            let deps = make::dependencies(targets, id);
+            match deps.is_empty() {
+                true => targets.get(id).time,
+                false => deps.into_iter().map(|d| s.leaf(targets, d)).max().flatten(),
+            }
+        })
+    }
+}
+
+impl Priority for NewestFirst {
+    fn order(&mut self, targets: &Targets, deps: &mut [NodeId]) {
+        deps.sort_by_cached_key(|&d| Reverse(self.leaf(targets, d)));
+    }
+}
+
+/// The targets with the longest chain of updates still to run below them
+/// first, for `--critical-path`, so that the work nothing else can overlap
+/// starts as early as it can.  Of the targets ready to be updated, those
+/// first whose update heads the longest chain up through the targets that
+/// depend on it.  Each update takes as long as it did in the previous
+/// run, as read from its report, or as long as the average one did if it
+/// was not run then.  Without timings every update counts alike.
+pub struct CriticalPath {
+    times: HashMap<String, Duration>,
+    average: Duration,
+    lengths: HashMap<NodeId, Duration>,
+    /// The targets depending on each target, found on first use.
+    dependents: Option<HashMap<NodeId, Vec<NodeId>>>,
+    /// How long the longest chain of updates starting at each target takes.
+    tails: HashMap<NodeId, Duration>,
+}
+
+impl CriticalPath {
+    /// Orders by the time updating each target took, named by target.
+    pub fn new(times: HashMap<String, Duration>) -> CriticalPath {
+        let average = match times.len() {
+            0 => Duration::from_secs(1),
+            n => times.values().sum::<Duration>() / n as u32,
+        };
+        CriticalPath { times, average, lengths: HashMap::new(), dependents: None, tails: HashMap::new() }
+    }
+
+    /// How long updating target `id` should take.
+    fn cost(&self, targets: &Targets, id: NodeId) -> Duration {
+        let t = targets.get(id);
+        if !t.fate.needs_build() || t.actions.is_empty() {
+            return Duration::ZERO;
+        }
+        self.times.get(t.name()).cloned().unwrap_or(self.average)
+    }
+
+    /// How long the longest chain of updates ending at target `id` takes.
+    fn length(&mut self, targets: &Targets, id: NodeId) -> Duration {
+        memoized(self, |s| &mut s.lengths, id, Duration::ZERO, |s| {
+            let below = make::dependencies(targets, id).into_iter()
+                .map(|d| s.length(targets, d))
+                .max()
+                .unwrap_or(Duration::ZERO);
+            below + s.cost(targets, id)
+        })
+    }
+
+    /// How long the longest chain of updates starting at target `id` and
+    /// going up through the targets depending on it takes.
+    fn tail(&mut self, targets: &Targets, id: NodeId) -> Duration {
+        memoized(self, |s| &mut s.tails, id, Duration::ZERO, |s| {
+            let dependents = s.dependents.get_or_insert_with(|| {
+                let mut dependents: HashMap<NodeId, Vec<NodeId>> = HashMap::new();
+                for t in targets.graph().ids() {
+                    for d in make::dependencies(targets, t) {
+                        dependents.entry(d).or_default().push(t);
+                    }
+                }
+                dependents
+            });
+            let above = dependents.get(&id).cloned().unwrap_or_default();
+            let above = above.into_iter().map(|t| s.tail(targets, t)).max().unwrap_or(Duration::ZERO);
+            above + s.cost(targets, id)
+        })
+    }
+}
+
+impl Priority for CriticalPath {
+    fn order(&mut self, targets: &Targets, deps: &mut [NodeId]) {
+        deps.sort_by_cached_key(|&d| Reverse(self.length(targets, d)));
+    }
+
+    fn order_ready(&mut self, targets: &Targets, ready: &mut [NodeId]) {
+        ready.sort_by_cached_key(|&t| Reverse(self.tail(targets, t)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::Action;
+    use jam_depgraph::Edge;
+    use std::rc::Rc;
+    use list::List;
+    use target::Fate;
+
+    #[test]
+    fn newest_sources_first() {
+        let mut ts = Targets::new();
+        let deps = |names: Vec<&str>| List::from(names);
+        ts.add_edges(&deps(vec!["prog"]), &deps(vec!["a.o", "b.o", "c.o", "d.o"]), Edge::Depends);
+        ts.add_edges(&deps(vec!["a.o"]), &deps(vec!["a.c", "util.h"]), Edge::Depends);
+        ts.add_edges(&deps(vec!["b.o"]), &deps(vec!["b.c"]), Edge::Depends);
+        ts.add_edges(&deps(vec!["c.o"]), &deps(vec!["c.c"]), Edge::Depends);
+        let now = SystemTime::now();
+        for &(name, age) in &[("a.c", 300), ("util.h", 10), ("b.c", 200), ("c.c", 20)] {
+            let id = ts.find(name).unwrap();
+            ts.get_mut(id).time = Some(now - Duration::from_secs(age));
+        }
+
+        let prog = ts.find("prog").unwrap();
+        let mut order = make::dependencies(&ts, prog);
+        Declared.order(&ts, &mut order);
+        let names = |order: &[NodeId]| order.iter().map(|&d| ts.get(d).name().to_string()).collect::<Vec<_>>();
+        assert_eq!(names(&order), vec!["a.o", "b.o", "c.o", "d.o"]);
+        NewestFirst::new().order(&ts, &mut order);
+        assert_eq!(names(&order), vec!["a.o", "c.o", "b.o", "d.o"]);
+    }
+
+    #[test]
+    fn longest_chains_first() {
+        let mut ts = Targets::new();
+        let deps = |names: Vec<&str>| List::from(names);
+        ts.add_edges(&deps(vec!["all"]), &deps(vec!["docs", "prog", "tests"]), Edge::Depends);
+        ts.add_edges(&deps(vec!["prog"]), &deps(vec!["main.o"]), Edge::Depends);
+        ts.add_edges(&deps(vec!["tests"]), &deps(vec!["test.o"]), Edge::Depends);
+        let build = Rc::new(Action::new("Build", vec![], "true"));
+        for name in &["docs", "prog", "main.o", "tests", "test.o"] {
+            ts.invoke(&build, &deps(vec![name]), &List::new());
+            let id = ts.find(name).unwrap();
+            ts.get_mut(id).fate = Fate::Update;
+        }
+        let all = ts.find("all").unwrap();
+        let names = |ts: &Targets, mut policy: CriticalPath| {
+            let mut order = make::dependencies(ts, all);
+            policy.order(ts, &mut order);
+            order.iter().map(|&d| ts.get(d).name().to_string()).collect::<Vec<_>>()
+        };
+
+        // Without timings, the chains of two updates come before docs.
+        assert_eq!(names(&ts, CriticalPath::new(HashMap::new())), vec!["prog", "tests", "docs"]);
+
+        let secs = |s: u64| Duration::from_secs(s);
+        let times: HashMap<String, Duration> = vec![("docs", 30), ("prog", 2), ("main.o", 6), ("test.o", 18)]
+            .into_iter().map(|(n, s)| (n.to_string(), secs(s))).collect();
+        // tests was not run before, so takes the average of 14s.
+        assert_eq!(names(&ts, CriticalPath::new(times.clone())), vec!["tests", "docs", "prog"]);
+
+        // Of the targets ready first, test.o heads 32s of updates.
+        let mut ready: Vec<NodeId> = ["docs", "main.o", "test.o"].iter().map(|n| ts.find(n).unwrap()).collect();
+        CriticalPath::new(times.clone()).order_ready(&ts, &mut ready);
+        assert_eq!(ready.iter().map(|&t| ts.get(t).name()).collect::<Vec<_>>(), vec!["test.o", "docs", "main.o"]);
+
+        let main = ts.find("main.o").unwrap();
+        ts.get_mut(main).fate = Fate::Stable;
+        let mut policy = CriticalPath::new(times);
+        assert_eq!(policy.length(&ts, all), secs(32));
+        assert_eq!(policy.length(&ts, ts.find("prog").unwrap()), secs(2));
+    }
+}
@@ -0,0 +1,132 @@
+use std::io::{self, Write};
+use command::Command;
+
+/// Writes updating actions to a POSIX shell script instead of running
+/// them, for `jam -o file`.  Each action is run by its own `/bin/sh -c`,
+/// exactly as jam would run it, and the script stops at the first
+/// failure unless the action was marked `ignore`.
+pub struct ScriptWriter<'a> {
+    out: &'a mut dyn Write,
+    target: Option<String>,
+}
+
+impl<'a> ScriptWriter<'a> {
+    pub fn new(out: &'a mut dyn Write) -> io::Result<ScriptWriter<'a>> {
+        writeln!(out, "#!/bin/sh")?;
+        writeln!(out, "set -e")?;
+        Ok(ScriptWriter { out, target: None })
+    }
+
+    pub fn command(&mut self, cmd: &Command) -> io::Result<()> {
+        if self.target.as_ref() != Some(&cmd.target) {
+            writeln!(self.out)?;
+            writeln!(self.out, "# {}", cmd.target)?;
+            self.target = Some(cmd.target.clone());
+        }
+        if !cmd.quiet() {
+            writeln!(self.out, "echo {}", quote(&format!("{} {}", cmd.action.name, cmd.target)))?;
+        }
+        let mut text = quote(&cmd.text);
+        if let Some(ref response) = cmd.response {
+            // The script makes a file of its own with mktemp, rather than
+            // writing to a name anyone could have guessed, and passes that.
+            let end = delimiter(&response.contents);
+            writeln!(self.out, "response=$(mktemp)")?;
+            write!(self.out, "cat > \"$response\" <<'{}'\n{}{}\n", end, response.contents, end)?;
+            let path = response.path.to_string_lossy();
+            let pieces: Vec<String> = cmd.text.split(&*path)
+                .map(|p| if p.is_empty() { String::new() } else { quote(p) })
+                .collect();
+            text = pieces.join("\"$response\"");
+        }
+        let ignore = if cmd.ignore() { " || true" } else { "" };
+        writeln!(self.out, "/bin/sh -c {}{}", text, ignore)?;
+        if cmd.response.is_some() {
+            writeln!(self.out, "rm -f \"$response\"")?;
+        }
+        Ok(())
+    }
+}
+
+/// A here-document delimiter that is not a line of `contents`.
+fn delimiter(contents: &str) -> String {
+    let mut end = "EOF".to_string();
+    let mut n = 0;
+    while contents.lines().any(|l| l == end) {
+        n += 1;
+        end = format!("EOF{}", n);
+    }
+    end
+}
+
+/// Quotes `s` as a single shell word.
+pub fn quote(s: &str) -> String {
+    let safe = !s.is_empty() && s.chars().all(|c| c.is_ascii_alphanumeric() || "+-./:=@_,%".contains(c));
+    if safe {
+        s.to_string()
+    } else {
+        format!("'{}'", s.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use action::{Action, Flags};
+    use list::List;
+    use std::rc::Rc;
+    use std::str;
+
+    fn command(flags: Vec<Flags>, target: &str, text: &str) -> Command {
+        Command {
+            action: Rc::new(Action::new("Cc", flags, "")),
+            target: target.to_string(),
+            targets: List::from(vec![target]),
+            sources: List::new(),
+            text: text.to_string(),
+            response: None,
+        }
+    }
+
+    #[test]
+    fn quoting() {
+        assert_eq!(quote("foo.o"), "foo.o");
+        assert_eq!(quote(""), "''");
+        assert_eq!(quote("a b"), "'a b'");
+        assert_eq!(quote("it's"), "'it'\\''s'");
+    }
+
+    #[test]
+    fn script_output() {
+        let mut out = Vec::new();
+        {
+            let mut w = ScriptWriter::new(&mut out).unwrap();
+            w.command(&command(vec![], "a.o", "\n\tcc -c 'a b.c'\n")).unwrap();
+            w.command(&command(vec![Flags::Quietly, Flags::Ignore], "a.o", "rm x")).unwrap();
+        }
+        assert_eq!(str::from_utf8(&out).unwrap(),
+                   "#!/bin/sh\nset -e\n\n# a.o\necho 'Cc a.o'\n\
+                    /bin/sh -c '\n\tcc -c '\\''a b.c'\\''\n'\n\
+                    /bin/sh -c 'rm x' || true\n");
+    }
+
+    #[test]
+    fn response_files_written_inline() {
+        let mut cmd = command(vec![], "prog", "ld @/tmp/r.rsp");
+        cmd.response = Some(::command::Response {
+            path: "/tmp/r.rsp".into(),
+            contents: "a.o\nb.o\n".to_string(),
+        });
+        let mut out = Vec::new();
+        ScriptWriter::new(&mut out).unwrap().command(&cmd).unwrap();
+        assert!(str::from_utf8(&out).unwrap().ends_with(
+            "response=$(mktemp)\ncat > \"$response\" <<'EOF'\na.o\nb.o\nEOF\n\
+             /bin/sh -c 'ld @'\"$response\"\nrm -f \"$response\"\n"));
+    }
+
+    #[test]
+    fn here_document_delimiter_not_in_contents() {
+        assert_eq!(delimiter("a.o\nEOF.o\n"), "EOF");
+        assert_eq!(delimiter("a.o\nEOF\nEOF1\n"), "EOF2");
+    }
+}
@@ -0,0 +1,132 @@
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use command;
+use env::Environment;
+use graph::Targets;
+use hash::{Digest, Hasher};
+use jam_depgraph::NodeId;
+use make;
+use target::Flags;
+
+/// The state file used when none is named with `--state`.
+pub const DEFAULT_PATH: &str = ".jamstate";
+
+/// What was recorded about a target the last time it was updated.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Entry {
+    /// The digest of the target's inputs, see `inputs`.
+    pub inputs: Option<Digest>,
+    /// The digest of the target's actions, see `commands`.
+    pub command: Option<Digest>,
+}
+
+/// Records kept between runs, one line per target, of the form
+/// `inputs command name` with `-` standing for a missing digest.
+#[derive(Debug)]
+pub struct State {
+    path: PathBuf,
+    entries: HashMap<String, Entry>,
+}
+
+impl State {
+    /// Reads the state file at `path`; a missing file is an empty state.
+    pub fn load<P: AsRef<Path>>(path: P) -> io::Result<State> {
+        let mut state = State { path: path.as_ref().to_path_buf(), entries: HashMap::new() };
+        let file = match File::open(&state.path) {
+            Ok(f) => f,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(state),
+            Err(e) => return Err(e),
+        };
+        for line in BufReader::new(file).lines() {
+            let line = line?;
+            let mut fields = line.splitn(3, ' ');
+            let (inputs, command, name) = match (fields.next(), fields.next(), fields.next()) {
+                (Some(i), Some(c), Some(n)) => (i, c, n),
+                _ => continue,
+            };
+            state.entries.insert(name.to_string(), Entry {
+                inputs: inputs.parse().ok(),
+                command: command.parse().ok(),
+            });
+        }
+        Ok(state)
+    }
+
+    /// Writes the state back, replacing the old file in one step.
+    pub fn save(&self) -> io::Result<()> {
+        let tmp = self.path.with_extension("tmp");
+        {
+            let mut f = io::BufWriter::new(File::create(&tmp)?);
+            let mut names: Vec<&String> = self.entries.keys().collect();
+            names.sort();
+            let digest = |d: Option<Digest>| d.map(|d| d.to_string()).unwrap_or_else(|| "-".to_string());
+            for name in names {
+                let e = &self.entries[name];
+                writeln!(f, "{} {} {}", digest(e.inputs), digest(e.command), name)?;
+            }
+            f.flush()?;
+        }
+        fs::rename(&tmp, &self.path)
+    }
+
+    pub fn get(&self, target: &str) -> Option<&Entry> {
+        self.entries.get(target)
+    }
+
+    pub fn entry(&mut self, target: &str) -> &mut Entry {
+        self.entries.entry(target.to_string()).or_default()
+    }
+}
+
+/// The digest of everything target `id` is built from: the names and
+/// contents of its dependencies and the headers they include.  Files are
+/// read afresh, so this is also right for dependencies built since binding.
+pub fn inputs(targets: &Targets, id: NodeId) -> Digest {
+    let mut h = Hasher::new();
+    for d in make::dependencies(targets, id) {
+        let dep = targets.get(d);
+        h.update_str(dep.name());
+        if !dep.has(Flags::NotFile) && h.update_file(dep.bound_name()).is_ok() {
+            h.update_str("");
+        } else {
+            h.update_str("-");
+        }
+    }
+    h.finish()
+}
+
+/// The digest of the actions that update target `id`, see
+/// `command::signature`.
+pub fn commands(targets: &Targets, env: &Environment, id: NodeId) -> Digest {
+    let mut h = Hasher::new();
+    for text in command::signature(targets, env, id) {
+        h.update_str(&text);
+    }
+    h.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+    use hash::Hasher;
+
+    #[test]
+    fn saving_and_loading() {
+        let dir = TempDir::new("state-test");
+        let path = dir.join("state");
+        let mut state = State::load(&path).unwrap();
+        assert!(state.get("a b.o").is_none());
+        state.entry("a b.o").inputs = Some(Hasher::new().finish());
+        state.entry("c.o").command = Some(Hasher::new().finish());
+        state.save().unwrap();
+
+        let loaded = State::load(&path).unwrap();
+        assert_eq!(loaded.get("a b.o").unwrap().inputs, Some(Hasher::new().finish()));
+        assert_eq!(loaded.get("a b.o").unwrap().command, None);
+        assert_eq!(loaded.get("c.o").unwrap().inputs, None);
+        assert_eq!(loaded.get("c.o").unwrap().command, Some(Hasher::new().finish()));
+    }
+}
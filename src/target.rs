@@ -0,0 +1,163 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::rc::Rc;
+use std::time::SystemTime;
+use action::Invocation;
+use env::Environment;
+use list::List;
+use variable::{Assign, Variable};
+
+/// The target flags set by the `ALWAYS`, `LEAVES`, `NOCARE`, `NOTFILE`,
+/// `NOUPDATE`, `PRECIOUS` and `TEMPORARY` builtin rules, and by `-t`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Flags {
+    Always,
+    Leaves,
+    NoCare,
+    NotFile,
+    NoUpdate,
+    /// Never removed when updating it fails or is interrupted.
+    Precious,
+    Temporary,
+    /// Named by `-t`: updated as if its file had just changed.
+    Touched,
+}
+
+impl fmt::Display for Flags {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Flags::Always => "ALWAYS",
+            Flags::Leaves => "LEAVES",
+            Flags::NoCare => "NOCARE",
+            Flags::NotFile => "NOTFILE",
+            Flags::NoUpdate => "NOUPDATE",
+            Flags::Precious => "PRECIOUS",
+            Flags::Temporary => "TEMPORARY",
+            Flags::Touched => "TOUCHED",
+        })
+    }
+}
+
+/// What binding decided must happen to a target, in increasing order of
+/// urgency.  Anything from `Touched` upwards gets built.
+#[derive(Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+pub enum Fate {
+    Init,
+    Making,
+    Stable,
+    Newer,
+    IsTmp,
+    Touched,
+    Missing,
+    NeedTmp,
+    Outdated,
+    Update,
+    CantFind,
+    CantMake,
+}
+
+impl Fate {
+    pub fn needs_build(&self) -> bool {
+        *self >= Fate::Touched && *self <= Fate::Update
+    }
+}
+
+impl fmt::Display for Fate {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Fate::Init => "init",
+            Fate::Making => "making",
+            Fate::Stable => "stable",
+            Fate::Newer => "newer",
+            Fate::IsTmp => "temp",
+            Fate::Touched => "touched",
+            Fate::Missing => "missing",
+            Fate::NeedTmp => "needtmp",
+            Fate::Outdated => "old",
+            Fate::Update => "update",
+            Fate::CantFind => "nofind",
+            Fate::CantMake => "nomake",
+        })
+    }
+}
+
+#[derive(Debug)]
+pub struct Target {
+    name: String,
+    settings: HashMap<String, Variable>,
+    pub flags: Vec<Flags>,
+    pub actions: Vec<Rc<Invocation>>,
+    pub bound: Option<String>,
+    pub time: Option<SystemTime>,
+    pub fate: Fate
+}
+
+impl Target {
+    pub fn new<T: ToString>(name: T) -> Target {
+        Target {
+            name: name.to_string(),
+            settings: HashMap::new(),
+            flags: Vec::new(),
+            actions: Vec::new(),
+            bound: None,
+            time: None,
+            fate: Fate::Init
+        }
+    }
+
+    pub fn name(&self) -> &str { &self.name }
+
+    /// The name the target was bound to, or its own name before binding.
+    pub fn bound_name(&self) -> &str {
+        self.bound.as_deref().unwrap_or(&self.name)
+    }
+
+    pub fn has(&self, flag: Flags) -> bool {
+        self.flags.contains(&flag)
+    }
+
+    /// Sets a target-specific variable, as in `name on target = value ;`.
+    /// Note that `?=` only consults the target-specific value, not the
+    /// global one.
+    pub fn set_var(&mut self, name: &str, op: Assign, value: &List) {
+        if let Some(v) = op.apply(self.settings.get_mut(name), value) {
+            self.settings.insert(name.to_string(), v);
+        }
+    }
+
+    pub fn var(&self, name: &str) -> Option<&Variable> {
+        self.settings.get(name)
+    }
+
+    pub fn settings(&self) -> &HashMap<String, Variable> { &self.settings }
+
+    pub fn settings_mut(&mut self) -> &mut HashMap<String, Variable> { &mut self.settings }
+
+    /// Pushes this target's variables as a scope on top of `env`, for use
+    /// while expanding its actions or evaluating an `on target` block.
+    pub fn scope<'a>(&self, env: &'a Environment<'a>) -> Environment<'a> {
+        Environment::new_with_settings(env, &self.settings)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn target_settings_shadow_globals() {
+        let mut global = Environment::new();
+        global.set("CCFLAGS", Assign::Set, &List::from(vec!["-O"]));
+        global.set("LINKLIBS", Assign::Set, &List::from(vec!["-lm"]));
+
+        let mut t = Target::new("foo.o");
+        t.set_var("CCFLAGS", Assign::Set, &List::from(vec!["-g"]));
+        t.set_var("CCFLAGS", Assign::Append, &List::from(vec!["-Wall"]));
+        t.set_var("LINKLIBS", Assign::Default, &List::from(vec!["-lz"]));
+
+        let scope = t.scope(&global);
+        assert_eq!(*scope["CCFLAGS"].value(), List::from(vec!["-g", "-Wall"]));
+        assert_eq!(*scope["LINKLIBS"].value(), List::from(vec!["-lz"]));
+        assert_eq!(*global["CCFLAGS"].value(), List::from(vec!["-O"]));
+    }
+}
@@ -0,0 +1,44 @@
+//! Helpers shared by the unit tests.
+
+use std::env;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// A fresh directory for one test, removed again when dropped.
+#[derive(Debug)]
+pub struct TempDir(PathBuf);
+
+impl TempDir {
+    /// Creates an empty directory named after `name`, this process and a
+    /// counter, so that tests running at once never share one.
+    pub fn new(name: &str) -> TempDir {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+        let n = COUNT.fetch_add(1, Ordering::SeqCst);
+        let path = env::temp_dir().join(format!("jam-{}-{}-{}", name, process::id(), n));
+        let _ = fs::remove_dir_all(&path);
+        fs::create_dir_all(&path).unwrap();
+        TempDir(path)
+    }
+
+    pub fn path(&self) -> &Path { &self.0 }
+
+    pub fn join<P: AsRef<Path>>(&self, name: P) -> PathBuf { self.0.join(name) }
+
+    /// The path of `name` in the directory, as a string for a target name.
+    pub fn name(&self, name: &str) -> String { self.join(name).to_string_lossy().into_owned() }
+
+    /// Writes `contents` to the file `name` in the directory.
+    pub fn write(&self, name: &str, contents: &str) -> PathBuf {
+        let path = self.join(name);
+        fs::write(&path, contents).unwrap();
+        path
+    }
+}
+
+impl Drop for TempDir {
+    fn drop(&mut self) {
+        let _ = fs::remove_dir_all(&self.0);
+    }
+}
@@ -1,7 +1,51 @@
+use std::fmt;
 use list::List;
 
-#[derive(Debug, PartialEq)]
+#[derive(Clone, Debug, PartialEq)]
 pub struct Variable {
     data: List,
     bindings: List
-}
\ No newline at end of file
+}
+
+impl Variable {
+    pub const fn new(data: List) -> Variable {
+        Variable {
+            data,
+            bindings: List::new()
+        }
+    }
+
+    pub fn value(&self) -> &List { &self.data }
+
+    pub fn value_mut(&mut self) -> &mut List { &mut self.data }
+}
+
+/// The assignment operators, `=`, `+=` and `?=` (or `default =`).
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Assign {
+    Set,
+    Append,
+    Default,
+}
+
+impl Assign {
+    /// Applies the assignment to an existing (possibly unset) value.
+    pub fn apply(&self, current: Option<&mut Variable>, value: &List) -> Option<Variable> {
+        match (*self, current) {
+            (Assign::Set, Some(v)) => { *v.value_mut() = value.clone(); None },
+            (Assign::Append, Some(v)) => { v.value_mut().extend(value); None },
+            (Assign::Default, Some(_)) => None,
+            (_, None) => Some(Variable::new(value.clone())),
+        }
+    }
+}
+
+impl fmt::Display for Assign {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str(match *self {
+            Assign::Set => "=",
+            Assign::Append => "+=",
+            Assign::Default => "?=",
+        })
+    }
+}
@@ -0,0 +1,113 @@
+use std::collections::HashSet;
+use std::io::{self, Write};
+use graph::Targets;
+use jam_depgraph::NodeId;
+use make;
+use target::{Fate, Flags};
+
+/// Prints, as an indented tree, why the bound target `name` will or will
+/// not be updated: the chain of dependencies leading to whatever made it
+/// out of date.
+pub fn explain(targets: &Targets, name: &str, out: &mut dyn Write) -> io::Result<()> {
+    match targets.find(name) {
+        Some(id) => explain_target(targets, id, 0, &mut HashSet::new(), out),
+        None => writeln!(out, "{}: unknown target", name),
+    }
+}
+
+fn explain_target(targets: &Targets, id: NodeId, depth: usize, seen: &mut HashSet<NodeId>,
+                  out: &mut dyn Write) -> io::Result<()> {
+    let t = targets.get(id);
+    let indent = "  ".repeat(depth);
+    writeln!(out, "{}{}: {}", indent, t.name(), t.fate)?;
+    if !seen.insert(id) {
+        return writeln!(out, "{}  (see above)", indent);
+    }
+
+    match t.fate {
+        Fate::Stable if depth == 0 => writeln!(out, "{}  up to date", indent)?,
+        Fate::Touched if t.has(Flags::Always) => writeln!(out, "{}  marked ALWAYS", indent)?,
+        Fate::Touched if t.has(Flags::Touched) => writeln!(out, "{}  touched with -t", indent)?,
+        Fate::Touched => writeln!(out, "{}  touched", indent)?,
+        Fate::Missing => writeln!(out, "{}  {} does not exist", indent, t.bound_name())?,
+        Fate::CantFind => writeln!(out, "{}  {} does not exist and has no actions", indent, t.bound_name())?,
+        _ => {}
+    }
+
+    let deps = make::dependency_edges(targets, id);
+    if t.fate == Fate::Outdated {
+        for &(d, via) in &deps {
+            let dep = targets.get(d);
+            if dep.time > t.time {
+                match via {
+                    Some(v) => writeln!(out, "{}  {} is newer, included by {}",
+                                        indent, dep.name(), targets.get(v).name())?,
+                    None => writeln!(out, "{}  {} is newer", indent, dep.name())?,
+                }
+            }
+        }
+    }
+    for &(d, _) in &deps {
+        let fate = targets.get(d).fate;
+        if fate.needs_build() || fate >= Fate::CantFind {
+            explain_target(targets, d, depth + 1, seen, out)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use testing::TempDir;
+    use action::Action;
+    use env::Environment;
+    use jam_depgraph::Edge;
+    use list::List;
+    use std::fs::File;
+    use std::rc::Rc;
+    use std::str;
+    use std::time::{Duration, SystemTime};
+    use variable::Assign;
+
+    fn file(dir: &::std::path::Path, name: &str, age: u64) {
+        let f = File::create(dir.join(name)).unwrap();
+        f.set_modified(SystemTime::now() - Duration::from_secs(age)).unwrap();
+    }
+
+    #[test]
+    fn explains_header_changes() {
+        let dir = TempDir::new("why-test");
+        file(dir.path(), "main.o", 100);
+        file(dir.path(), "main.c", 200);
+        file(dir.path(), "main.h", 10);
+        file(dir.path(), "prog.def", 300);
+
+        let mut env = Environment::new();
+        env.set("SEARCH", Assign::Set, &List::from(vec![dir.path().to_str().unwrap()]));
+        let mut ts = Targets::new();
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["main.o"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["main.o"]), &List::from(vec!["main.c"]), Edge::Depends);
+        ts.add_edges(&List::from(vec!["main.c"]), &List::from(vec!["main.h"]), Edge::Includes);
+        ts.add_edges(&List::from(vec!["prog"]), &List::from(vec!["prog.def"]), Edge::Depends);
+        let build = Rc::new(Action::new("Build", vec![], ""));
+        for name in &["prog", "main.o"] {
+            ts.invoke(&build, &List::from(vec![*name]), &List::new());
+        }
+        make::bind(&mut ts, &env, &List::from(vec!["prog", "prog.def"]));
+
+        let mut out = Vec::new();
+        explain(&ts, "prog", &mut out).unwrap();
+        explain(&ts, "prog.def", &mut out).unwrap();
+        explain(&ts, "nothing", &mut out).unwrap();
+        assert_eq!(str::from_utf8(&out).unwrap(), "\
+prog: missing
+  prog does not exist
+  main.o: old
+    main.h is newer, included by main.c
+prog.def: stable
+  up to date
+nothing: unknown target
+");
+    }
+}